@@ -3,11 +3,12 @@ use twilight_mention::Mention;
 use twilight_model::gateway::payload::incoming::MessageCreate;
 
 use crate::{
-	EventWithContext, handle_message,
+	EventWithContext, claim_command, handle_message,
 	utils::{args, consts::FIRMAMENT_SERVER, dynroles::upsert_vanity_role},
 };
 
 handle_message!(should_obey, on_badge_cmd);
+claim_command!("badge");
 
 async fn on_badge_cmd(event: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
 	if let Some(args) = event.content.strip_prefix("!badge ") {
@@ -0,0 +1,246 @@
+use std::collections::VecDeque;
+
+use twilight_http::Client;
+use twilight_model::{
+	channel::Message,
+	id::{
+		Id,
+		marker::{MessageMarker, UserMarker},
+	},
+};
+
+use super::{
+	LastNumber, NumberFormat,
+	machine::{CountingState, Incoming, Rejection, parse_repost},
+	milestones::Milestones,
+	parse_count,
+	strikes::StrikeBook,
+};
+use crate::utils::consts::COUNTING_CHANNEL;
+
+/// Fetch every message ever sent in the counting channel, oldest first.
+pub async fn fetch_history(client: &Client) -> eyre::Result<Vec<Message>> {
+	const PAGE_SIZE: u16 = 100;
+	let mut messages = Vec::new();
+	let mut before = None;
+	loop {
+		let request = client.channel_messages(COUNTING_CHANNEL);
+		let page = match before {
+			Some(before) => request.before(before).limit(PAGE_SIZE).await?,
+			None => request.limit(PAGE_SIZE).await?,
+		}
+		.model()
+		.await?;
+		let Some(last) = page.last() else {
+			break;
+		};
+		before = Some(last.id);
+		let is_last_page = page.len() < PAGE_SIZE as usize;
+		messages.extend(page);
+		if is_last_page {
+			break;
+		}
+	}
+	messages.reverse();
+	Ok(messages)
+}
+
+/// The parts of a message in the counting channel that replaying needs.
+pub struct Posted<'a> {
+	pub id: Id<MessageMarker>,
+	pub author: Id<UserMarker>,
+	pub bot: bool,
+	pub content: &'a str,
+	/// When the message was sent, in seconds since the unix epoch.
+	pub at: u64,
+}
+
+impl<'a> From<&'a Message> for Posted<'a> {
+	fn from(message: &'a Message) -> Self {
+		Posted {
+			id: message.id,
+			author: message.author.id,
+			bot: message.author.bot,
+			content: &message.content,
+			at: message.timestamp.as_secs() as u64,
+		}
+	}
+}
+
+/// The outcome of replaying the counting rules over the channel history.
+pub struct Replay {
+	/// The last correct counts, oldest first. The last one is the true current count.
+	pub recent: VecDeque<LastNumber>,
+	/// The strikes, streaks and highest counts everyone would have had.
+	pub strikes: StrikeBook,
+	pub violations: Vec<Violation>,
+}
+
+pub struct Violation {
+	pub message_id: Id<MessageMarker>,
+	pub user: Id<UserMarker>,
+	pub kind: Rejection,
+}
+
+/// Replay the counting rules over the whole history, oldest message first.
+pub fn replay<'a>(history: impl IntoIterator<Item = Posted<'a>>) -> Replay {
+	replay_from(history, false)
+}
+
+/// Replay the counting rules over the latest part of the history, oldest message first. What came before is unknown, so
+/// the first count is taken as correct.
+pub fn resume<'a>(history: impl IntoIterator<Item = Posted<'a>>) -> Replay {
+	replay_from(history, true)
+}
+
+fn replay_from<'a>(history: impl IntoIterator<Item = Posted<'a>>, trust_first: bool) -> Replay {
+	let mut state = CountingState {
+		recent: VecDeque::new(),
+		strikes: StrikeBook::default(),
+		milestones: Milestones::default(),
+	};
+	let mut violations = vec![];
+	for message in history {
+		if message.bot {
+			// The deleted or edited count is gone from the history, so its repost stands in for it
+			if let Some((count, user)) = parse_repost(message.content) {
+				state.resync(LastNumber {
+					user,
+					count,
+					message_id: message.id,
+					number_format: NumberFormat::Decimal,
+					reposted: true,
+				});
+			}
+			continue;
+		}
+		if message.content.starts_with("!count ") {
+			continue;
+		}
+		if trust_first && state.current().is_none() {
+			if let Some(first) = parse_count(message.author, message.id, message.content) {
+				state.resync(first);
+			}
+			continue;
+		}
+		let incoming = Incoming {
+			message_id: message.id,
+			user: message.author,
+			content: message.content,
+		};
+		let rejection = state.judge(&incoming).err();
+		state.on_message(incoming, message.at);
+		if let Some(kind) = rejection {
+			violations.push(Violation {
+				message_id: message.id,
+				user: message.author,
+				kind,
+			});
+		}
+	}
+	Replay {
+		recent: state.recent,
+		strikes: state.strikes,
+		violations,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use twilight_model::id::Id;
+
+	use crate::features::counting::{
+		LastNumber, NumberFormat,
+		history::{Posted, replay, resume},
+		machine::{CountingState, Rejection, repost},
+		milestones::Milestones,
+	};
+
+	fn posted(id: u64, author: u64, content: &str) -> Posted<'_> {
+		Posted {
+			id: Id::new(id),
+			author: Id::new(author),
+			bot: author == BOT,
+			content,
+			at: 0,
+		}
+	}
+
+	const BOT: u64 = 99;
+
+	#[test]
+	fn test_replay_follows_reposts() {
+		let reposted = |count, user, action| {
+			let last = LastNumber {
+				user: Id::new(user),
+				count,
+				message_id: Id::new(1),
+				number_format: NumberFormat::Decimal,
+				reposted: false,
+			};
+			repost(&last, action)
+		};
+		let deleted = reposted(2, 2, "deleted");
+		let edited = reposted(4, 2, "edited");
+		// 2 was deleted and 4 edited, then counting went on
+		let history = [
+			posted(1, 1, "1"),
+			posted(3, BOT, &deleted),
+			posted(4, BOT, "<@2> that was not quite right."),
+			posted(5, 1, "3"),
+			posted(6, 2, "lol"),
+			posted(7, BOT, &edited),
+			posted(8, 1, "5"),
+			posted(9, 2, "6"),
+		];
+		let replay = replay(history);
+		assert_eq!(replay.recent.back().unwrap().count, 6);
+		assert!(matches!(
+			&replay.violations[..],
+			[it] if it.message_id == Id::new(6) && it.kind == Rejection::NotANumber
+		));
+		let mut strikes = replay.strikes;
+		assert_eq!(strikes.user(Id::new(1)).highest, 5);
+		assert_eq!(strikes.user(Id::new(2)).streak, 1);
+	}
+
+	#[test]
+	fn test_resume_trusts_the_first_count() {
+		let history = [
+			posted(1, 2, "so"),
+			posted(2, 1, "41"),
+			posted(3, 2, "42"),
+			posted(4, 2, "43"),
+			posted(5, 1, "43"),
+		];
+		let replay = resume(history);
+		assert_eq!(replay.recent.back().unwrap().count, 43);
+		assert_eq!(replay.violations.len(), 1);
+		assert_eq!(replay.violations[0].kind, Rejection::SameUser);
+	}
+
+	#[test]
+	fn test_deleting_a_resumed_repost_is_ignored() {
+		let deleted = repost(
+			&LastNumber {
+				user: Id::new(2),
+				count: 2,
+				message_id: Id::new(2),
+				number_format: NumberFormat::Decimal,
+				reposted: false,
+			},
+			"deleted",
+		);
+		let history = [posted(1, 1, "1"), posted(3, BOT, &deleted)];
+		let replay = resume(history);
+		let mut state = CountingState {
+			recent: replay.recent,
+			strikes: replay.strikes,
+			milestones: Milestones::default(),
+		};
+		assert!(state.on_delete(Id::new(3), 0).is_empty());
+		assert!(state.on_edit(Id::new(3), "lol", 0).is_empty());
+		let current = state.current().unwrap();
+		assert_eq!((current.user, current.count), (Id::new(2), 2));
+	}
+}
@@ -0,0 +1,434 @@
+use std::{borrow::Cow, collections::VecDeque, fmt::Display, time::Duration};
+
+use twilight_model::id::{
+	Id,
+	marker::{MessageMarker, UserMarker},
+};
+
+use super::{
+	LastNumber, NumberFormat, RECENT_COUNT_LIMIT,
+	milestones::Milestones,
+	parse_count,
+	strikes::{Offense, Penalty, StrikeBook},
+};
+use crate::utils::consts::{COUNTING_CHANNEL, FIRMAMENT_SERVER};
+
+/// Everything the counting rules need to know. Pure: all changes to Discord are returned as [`Effect`]s instead.
+pub struct CountingState {
+	/// The last correct counts, oldest first. The last one is the current count.
+	pub recent: VecDeque<LastNumber>,
+	pub strikes: StrikeBook,
+	pub milestones: Milestones,
+}
+
+/// A new message in the counting channel.
+pub struct Incoming<'a> {
+	pub message_id: Id<MessageMarker>,
+	pub user: Id<UserMarker>,
+	pub content: &'a str,
+}
+
+/// Why a message is not the next count.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rejection {
+	NotANumber,
+	WrongNumber { expected: u64, given: u64 },
+	SameUser,
+}
+
+impl Display for Rejection {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Rejection::NotANumber => write!(f, "not a number"),
+			Rejection::WrongNumber { expected, given } => {
+				write!(f, "expected {expected}, but got {given}")
+			}
+			Rejection::SameUser => write!(f, "counted twice in a row"),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Effect {
+	Delete(Id<MessageMarker>),
+	React(Id<MessageMarker>, Cow<'static, str>),
+	/// Post a message into the counting channel.
+	Announce(String),
+	Timeout(Id<UserMarker>, Duration),
+	/// Give the user the ladder role for this count.
+	UpdateLadder(Id<UserMarker>, u64),
+}
+
+impl CountingState {
+	pub fn current(&self) -> Option<&LastNumber> {
+		self.recent.back()
+	}
+
+	/// Check whether a message is the next count, without changing anything.
+	pub fn judge(&self, message: &Incoming) -> Result<LastNumber, Rejection> {
+		let current = self.current();
+		let given = parse_count(message.user, message.message_id, message.content)
+			.ok_or(Rejection::NotANumber)?;
+		if current.is_some_and(|it| it.user == given.user) {
+			return Err(Rejection::SameUser);
+		}
+		let expected = current.map_or(1, |it| it.count + 1);
+		if given.count != expected {
+			return Err(Rejection::WrongNumber {
+				expected,
+				given: given.count,
+			});
+		}
+		Ok(given)
+	}
+
+	/// Take a count as the current one without checking it, like one reposted by the bot.
+	pub fn resync(&mut self, count: LastNumber) {
+		if self.current().is_some_and(|it| it.count == count.count) {
+			return;
+		}
+		self.recent.push_back(count);
+		if self.recent.len() > RECENT_COUNT_LIMIT + 1 {
+			self.recent.pop_front();
+		}
+	}
+
+	pub fn on_message(&mut self, message: Incoming, now: u64) -> Vec<Effect> {
+		let mut effects = vec![];
+		let Ok(given) = self.judge(&message) else {
+			effects.push(Effect::Delete(message.message_id));
+			self.penalize(message.user, Offense::Mistake, now, &mut effects);
+			return effects;
+		};
+
+		tracing::info!("Incrementing counter to {given:?}");
+		let count = given.count;
+		effects.push(Effect::React(
+			message.message_id,
+			match given.number_format {
+				NumberFormat::Decimal => "🔢".into(),
+				_ => "🤓".into(),
+			},
+		));
+		self.recent.push_back(given);
+		if self.recent.len() > RECENT_COUNT_LIMIT + 1 {
+			self.recent.pop_front();
+		}
+
+		let record = self.strikes.user(message.user);
+		record.highest = record.highest.max(count);
+		if record.record_count() {
+			effects.push(Effect::React(message.message_id, "🛟".into()));
+		}
+		let mut celebration = None;
+		for milestone in self.milestones.matching(count) {
+			effects.push(Effect::React(
+				message.message_id,
+				milestone.reaction.clone().into(),
+			));
+			celebration = celebration.or(milestone.message.as_deref());
+		}
+		if let Some(celebration) = celebration {
+			effects.push(Effect::Announce(
+				celebration
+					.replace("{user}", &format!("<@{}>", message.user))
+					.replace("{count}", &count.to_string()),
+			));
+		}
+		effects.push(Effect::UpdateLadder(message.user, count));
+		effects
+	}
+
+	pub fn on_delete(&mut self, message_id: Id<MessageMarker>, now: u64) -> Vec<Effect> {
+		let mut effects = vec![];
+		// Staff cleaning up a repost of the bot is not the counter deleting their count
+		let Some(current) = self
+			.current()
+			.filter(|it| it.message_id == message_id && !it.reposted)
+		else {
+			return effects;
+		};
+		let user = current.user;
+		effects.push(Effect::Announce(repost(current, "deleted")));
+		if self.strikes.mark_penalized(message_id) {
+			self.penalize(user, Offense::Deletion, now, &mut effects);
+		}
+		effects
+	}
+
+	/// Purges are done by staff, so nobody gets punished, but the count still needs to stay visible in the channel.
+	pub fn on_delete_bulk(&mut self, message_ids: &[Id<MessageMarker>]) -> Vec<Effect> {
+		let current_id = self.current().map(|it| it.message_id);
+		self.recent.retain(|it| {
+			Some(it.message_id) == current_id || !message_ids.contains(&it.message_id)
+		});
+		match self.current() {
+			Some(current) if message_ids.contains(&current.message_id) => {
+				tracing::info!("Bulk delete removed the current count {current:?}");
+				vec![Effect::Announce(repost(current, "purged"))]
+			}
+			_ => vec![],
+		}
+	}
+
+	pub fn on_edit(
+		&mut self,
+		message_id: Id<MessageMarker>,
+		content: &str,
+		now: u64,
+	) -> Vec<Effect> {
+		let mut effects = vec![];
+		let Some(position) = self
+			.recent
+			.iter()
+			.position(|it| it.message_id == message_id && !it.reposted)
+		else {
+			return effects;
+		};
+		let original = &self.recent[position];
+		if parse_count(original.user, message_id, content)
+			.is_some_and(|it| it.count == original.count)
+		{
+			return effects;
+		}
+		tracing::info!("Counting message {original:?} was edited to {content:?}");
+		let user = original.user;
+		if position + 1 == self.recent.len() {
+			effects.push(Effect::Announce(repost(original, "edited")));
+		} else {
+			// Reposting an older number would make it look like the current count, so only link to it instead.
+			effects.push(Effect::Announce(format!(
+				"Hi, it is me — cute little mouse — again. Someone has edited an older message in this channel. <@{}> originally posted {} in https://discord.com/channels/{}/{}/{}.",
+				original.user, original.count, FIRMAMENT_SERVER, COUNTING_CHANNEL, original.message_id
+			)));
+		}
+		if self.strikes.mark_penalized(message_id) {
+			self.penalize(user, Offense::Edit, now, &mut effects);
+		}
+		effects
+	}
+
+	/// Hand out a strike and apply whatever penalty the strike count calls for.
+	fn penalize(
+		&mut self,
+		user: Id<UserMarker>,
+		offense: Offense,
+		now: u64,
+		effects: &mut Vec<Effect>,
+	) {
+		let record = self.strikes.user(user);
+		let penalty = record.offend(offense, now);
+		tracing::info!("Penalizing {user} for {offense:?} with {penalty:?}");
+		let message = match penalty {
+			Penalty::Saved => format!(
+				"<@{user}> that was not quite right, but you had a save. Saves left: {}.",
+				record.saves
+			),
+			Penalty::Warning => format!(
+				"<@{user}> that was not quite right. This is only a warning, but further mistakes will get you timed out."
+			),
+			Penalty::Timeout(duration) => {
+				effects.push(Effect::Timeout(user, duration));
+				format!(
+					"<@{user}> has been timed out for {} minutes. Active strikes: {}.",
+					duration.as_secs() / 60,
+					record.strikes
+				)
+			}
+		};
+		effects.push(Effect::Announce(message));
+	}
+}
+
+/// Post the current number again, so the channel history stays consistent after the original message went away.
+pub fn repost(current: &LastNumber, action: &str) -> String {
+	format!(
+		"{} — Hi, it is me — cute little mouse — and i am here to provide you with some help. It has come to my attention that recently someone has {} a message in this channel. Not to worry, I have remembered their number. <@{}> recently posted {}.",
+		current.count, action, current.user, current.count
+	)
+}
+
+/// Read a [`repost`] back, returning the reposted count and who originally counted it.
+pub fn parse_repost(content: &str) -> Option<(u64, Id<UserMarker>)> {
+	let (count, rest) = content.split_once(" — ")?;
+	let mention = rest.strip_suffix(&format!(" recently posted {count}."))?;
+	let user = mention
+		.rsplit_once("<@")?
+		.1
+		.strip_suffix('>')?
+		.parse()
+		.ok()?;
+	Some((count.parse().ok()?, user))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::VecDeque, time::Duration};
+
+	use twilight_model::id::Id;
+
+	use crate::features::counting::{
+		machine::{CountingState, Effect, Incoming},
+		milestones::Milestones,
+		strikes::StrikeBook,
+	};
+
+	fn new_state() -> CountingState {
+		CountingState {
+			recent: VecDeque::new(),
+			strikes: StrikeBook::default(),
+			milestones: Milestones::default(),
+		}
+	}
+
+	fn send(state: &mut CountingState, message_id: u64, user: u64, content: &str) -> Vec<Effect> {
+		state.on_message(
+			Incoming {
+				message_id: Id::new(message_id),
+				user: Id::new(user),
+				content,
+			},
+			0,
+		)
+	}
+
+	fn is_accepted(effects: &[Effect]) -> bool {
+		effects
+			.iter()
+			.any(|it| matches!(it, Effect::UpdateLadder(_, _)))
+	}
+
+	#[test]
+	fn test_counts_in_sequence() {
+		let mut state = new_state();
+		let effects = send(&mut state, 1, 1, "1");
+		assert_eq!(
+			effects,
+			vec![
+				Effect::React(Id::new(1), "🔢".into()),
+				Effect::UpdateLadder(Id::new(1), 1)
+			]
+		);
+		assert!(is_accepted(&send(&mut state, 2, 2, "2 this is a comment")));
+		assert!(is_accepted(&send(&mut state, 3, 1, "3")));
+		assert_eq!(state.current().unwrap().count, 3);
+		assert_eq!(state.strikes.user(Id::new(1)).streak, 2);
+	}
+
+	#[test]
+	fn test_same_user_repeat() {
+		let mut state = new_state();
+		send(&mut state, 1, 1, "1");
+		let effects = send(&mut state, 2, 1, "2");
+		assert_eq!(effects[0], Effect::Delete(Id::new(2)));
+		assert!(!is_accepted(&effects));
+		assert_eq!(state.current().unwrap().count, 1);
+		assert_eq!(state.strikes.user(Id::new(1)).strikes, 1);
+	}
+
+	#[test]
+	fn test_wrong_numbers() {
+		let mut state = new_state();
+		send(&mut state, 1, 1, "1");
+		for (message_id, content) in [(2, "3"), (3, "1"), (4, "two"), (5, "")] {
+			let effects = send(&mut state, message_id, 2, content);
+			assert_eq!(effects[0], Effect::Delete(Id::new(message_id)));
+		}
+		assert_eq!(state.current().unwrap().count, 1);
+		// Warning, then timeouts for every further strike
+		let effects = send(&mut state, 6, 2, "5");
+		assert!(effects.contains(&Effect::Timeout(Id::new(2), Duration::from_days(7))));
+		assert!(is_accepted(&send(&mut state, 7, 2, "2")));
+	}
+
+	#[test]
+	fn test_number_formats() {
+		let mut state = new_state();
+		let formats = [
+			("1", "🔢"),
+			("0b10", "🤓"),
+			("0x3", "🤓"),
+			("4h", "🤓"),
+			("0o5", "🤓"),
+			("0u000000", "🤓"),
+			("7", "🔢"),
+		];
+		for (index, (content, reaction)) in formats.into_iter().enumerate() {
+			let message_id = index as u64 + 1;
+			let effects = send(&mut state, message_id, message_id % 2 + 1, content);
+			assert_eq!(
+				effects[0],
+				Effect::React(Id::new(message_id), reaction.into())
+			);
+		}
+		assert_eq!(state.current().unwrap().count, 7);
+		assert_eq!(send(&mut state, 8, 2, "0u0")[0], Effect::Delete(Id::new(8)));
+	}
+
+	#[test]
+	fn test_deletions() {
+		let mut state = new_state();
+		send(&mut state, 1, 1, "1");
+		send(&mut state, 2, 2, "2");
+		assert_eq!(state.on_delete(Id::new(1), 0), vec![]);
+		let effects = state.on_delete(Id::new(2), 0);
+		assert!(matches!(&effects[0], Effect::Announce(it) if it.starts_with("2 — ")));
+		// Deletions count double, so they skip the warning
+		assert!(effects.contains(&Effect::Timeout(Id::new(2), Duration::from_mins(10))));
+		assert!(is_accepted(&send(&mut state, 3, 1, "3")));
+	}
+
+	#[test]
+	fn test_bulk_deletions_are_not_punished() {
+		let mut state = new_state();
+		send(&mut state, 1, 1, "1");
+		send(&mut state, 2, 2, "2");
+		let effects = state.on_delete_bulk(&[Id::new(1), Id::new(2)]);
+		assert!(matches!(&effects[..], [Effect::Announce(it)] if it.starts_with("2 — ")));
+		assert_eq!(state.recent.len(), 1);
+		assert_eq!(state.strikes.user(Id::new(2)).strikes, 0);
+	}
+
+	#[test]
+	fn test_edits() {
+		let mut state = new_state();
+		send(&mut state, 1, 1, "1");
+		send(&mut state, 2, 2, "2");
+		assert_eq!(state.on_edit(Id::new(2), "2 (typo fixed)", 0), vec![]);
+		let effects = state.on_edit(Id::new(1), "lol", 0);
+		assert!(matches!(&effects[0], Effect::Announce(it) if it.starts_with("Hi")));
+		assert_eq!(state.strikes.user(Id::new(1)).strikes, 2);
+		let effects = state.on_edit(Id::new(2), "69", 0);
+		assert!(matches!(&effects[0], Effect::Announce(it) if it.starts_with("2 — ")));
+		assert_eq!(state.current().unwrap().count, 2);
+	}
+
+	#[test]
+	fn test_configured_milestones() {
+		let mut state = new_state();
+		state.milestones = serde_json::from_str(
+			r#"[
+				{ "rule": { "exactly": 2 }, "reaction": "✌️", "message": "{user} reached {count}" },
+				{ "rule": { "multiple_of": 2 }, "reaction": "🎉" }
+			]"#,
+		)
+		.unwrap();
+		assert_eq!(send(&mut state, 1, 1, "1").len(), 2);
+		let effects = send(&mut state, 2, 2, "2");
+		assert!(effects.contains(&Effect::React(Id::new(2), "✌️".into())));
+		assert!(effects.contains(&Effect::React(Id::new(2), "🎉".into())));
+		assert!(effects.contains(&Effect::Announce("<@2> reached 2".to_owned())));
+		assert_eq!(state.strikes.user(Id::new(2)).highest, 2);
+	}
+
+	#[test]
+	fn test_edit_then_delete_is_punished_once() {
+		let mut state = new_state();
+		send(&mut state, 1, 1, "1");
+		state.on_edit(Id::new(1), "lol", 0);
+		state.on_edit(Id::new(1), "lmao", 0);
+		let effects = state.on_delete(Id::new(1), 0);
+		assert!(matches!(&effects[..], [Effect::Announce(it)] if it.starts_with("1 — ")));
+		assert_eq!(state.strikes.user(Id::new(1)).strikes, 2);
+	}
+}
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utils::persist;
+
+const MILESTONES_PATH: &str = "counting/milestones.json";
+
+/// Which counts a milestone is for.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MilestoneRule {
+	Exactly(u64),
+	MultipleOf(u64),
+	/// Numbers from `min` on that read the same backwards, like 12321.
+	Palindrome {
+		min: u64,
+	},
+	/// Numbers from `min` on made of a single repeated digit, like 7777.
+	Repdigit {
+		min: u64,
+	},
+}
+
+impl MilestoneRule {
+	fn matches(&self, n: u64) -> bool {
+		match *self {
+			MilestoneRule::Exactly(number) => n == number,
+			MilestoneRule::MultipleOf(factor) => n != 0 && factor != 0 && n % factor == 0,
+			MilestoneRule::Palindrome { min } => n >= min && is_palindrome(n),
+			MilestoneRule::Repdigit { min } => n >= min && is_repdigit(n),
+		}
+	}
+}
+
+/// A special number that gets celebrated when someone counts to it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Milestone {
+	pub rule: MilestoneRule,
+	pub reaction: String,
+	/// Posted into the counting channel. `{user}` and `{count}` get replaced.
+	#[serde(default)]
+	pub message: Option<String>,
+}
+
+/// All milestones, configured in `counting/milestones.json`. If multiple match, all reactions are added, but only the
+/// first message is posted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Milestones(Vec<Milestone>);
+
+impl Default for Milestones {
+	fn default() -> Self {
+		let milestone = |rule, reaction: &str, message: Option<&str>| Milestone {
+			rule,
+			reaction: reaction.to_owned(),
+			message: message.map(ToOwned::to_owned),
+		};
+		Milestones(vec![
+			milestone(
+				MilestoneRule::Exactly(1337),
+				"😎",
+				Some("{user} is elite. {count}!"),
+			),
+			milestone(
+				MilestoneRule::Exactly(4096),
+				"💾",
+				Some("{count}. A nice round number, if you are a computer."),
+			),
+			milestone(
+				MilestoneRule::MultipleOf(1000),
+				"🎉",
+				Some("{user} just counted to {count}! Keep going!"),
+			),
+			milestone(MilestoneRule::Repdigit { min: 100 }, "🎰", None),
+			milestone(MilestoneRule::Palindrome { min: 100 }, "🪞", None),
+		])
+	}
+}
+
+impl Milestones {
+	/// Load the milestones. If there is no config yet, the defaults are written out first, so there is one to edit.
+	pub async fn load() -> eyre::Result<Milestones> {
+		if !tokio::fs::try_exists(MILESTONES_PATH).await? {
+			persist::save_json(MILESTONES_PATH, &Milestones::default()).await?;
+		}
+		persist::load_json(MILESTONES_PATH).await
+	}
+
+	pub fn matching(&self, count: u64) -> impl Iterator<Item = &Milestone> {
+		self.0.iter().filter(move |it| it.rule.matches(count))
+	}
+}
+
+fn is_palindrome(n: u64) -> bool {
+	let digits = n.to_string();
+	digits.chars().eq(digits.chars().rev())
+}
+
+fn is_repdigit(n: u64) -> bool {
+	let digits = n.to_string();
+	digits.chars().all(|it| digits.starts_with(it))
+}
@@ -0,0 +1,478 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+use twilight_http::{Client, request::channel::reaction::RequestReactionType};
+use twilight_model::{
+	gateway::payload::incoming::{MessageCreate, MessageDelete, MessageDeleteBulk, MessageUpdate},
+	id::{
+		Id,
+		marker::{MessageMarker, RoleMarker, UserMarker},
+	},
+	util::Timestamp,
+};
+
+use self::{
+	machine::{CountingState, Effect, Incoming},
+	milestones::Milestones,
+	strikes::StrikeBook,
+};
+use crate::{
+	EventWithContext, HeliosCache, claim_command, handle, handle_message,
+	utils::{
+		args,
+		consts::{COUNTING_CHANNEL, FIRMAMENT_SERVER, THE_NO_ONE},
+		dynroles::upsert_vanity_role,
+	},
+};
+
+mod history;
+mod machine;
+mod milestones;
+mod strikes;
+
+handle_message!(should_reply, on_count);
+handle_message!(should_obey, on_count_command);
+claim_command!("count");
+handle!(MessageDelete, on_delete);
+handle!(MessageDeleteBulk, on_delete_bulk);
+handle!(MessageUpdate, on_edit);
+async fn on_delete(event: EventWithContext<&MessageDelete>) -> eyre::Result<()> {
+	if event.channel_id != COUNTING_CHANNEL {
+		return Ok(());
+	}
+
+	let mut state = counting_state(&event.client, None).await?;
+	let effects = state.on_delete(event.id, unix_now());
+	run_effects(&event.client, &event.cache, state, effects).await
+}
+
+async fn on_delete_bulk(event: EventWithContext<&MessageDeleteBulk>) -> eyre::Result<()> {
+	if event.channel_id != COUNTING_CHANNEL {
+		return Ok(());
+	}
+
+	let mut state = counting_state(&event.client, None).await?;
+	let effects = state.on_delete_bulk(&event.ids);
+	run_effects(&event.client, &event.cache, state, effects).await
+}
+
+async fn on_edit(event: EventWithContext<&MessageUpdate>) -> eyre::Result<()> {
+	if event.channel_id != COUNTING_CHANNEL {
+		return Ok(());
+	}
+
+	let mut state = counting_state(&event.client, None).await?;
+	let effects = state.on_edit(event.id, &event.content, unix_now());
+	run_effects(&event.client, &event.cache, state, effects).await
+}
+
+async fn on_count(event: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
+	if event.channel_id != COUNTING_CHANNEL || event.content.starts_with("!count ") {
+		return Ok(());
+	}
+
+	let mut state = counting_state(&event.client, Some(event.id)).await?;
+	let effects = state.on_message(
+		Incoming {
+			message_id: event.id,
+			user: event.author.id,
+			content: &event.content,
+		},
+		unix_now(),
+	);
+	run_effects(&event.client, &event.cache, state, effects).await
+}
+
+/// Lock the counting state, loading it from disk and the channel history first if needed.
+async fn counting_state(
+	client: &Client,
+	exclude: Option<Id<MessageMarker>>,
+) -> eyre::Result<MappedMutexGuard<'static, CountingState>> {
+	static COUNTING_STATE: Mutex<Option<CountingState>> = Mutex::const_new(None);
+	let mut holder = COUNTING_STATE.lock().await;
+	match &*holder {
+		Some(state) => {
+			tracing::info!("Found existing counter {:?}", state.current());
+		}
+		None => {
+			let messages = client
+				.channel_messages(COUNTING_CHANNEL)
+				.limit(RECENT_COUNT_LIMIT as u16)
+				.await?
+				.model()
+				.await?;
+			// Discord returns the newest message first
+			let history = messages
+				.iter()
+				.rev()
+				.filter(|it| Some(it.id) != exclude)
+				.map(history::Posted::from);
+			let mut recent = history::resume(history).recent;
+			if recent.is_empty() {
+				recent.push_back(LastNumber {
+					user: THE_NO_ONE,
+					count: 0,
+					message_id: Id::new(1),
+					number_format: NumberFormat::Decimal,
+					reposted: false,
+				});
+			}
+			let state = CountingState {
+				recent,
+				strikes: StrikeBook::load().await?,
+				milestones: Milestones::load().await?,
+			};
+			tracing::info!("Loaded counter from channel {:?}", state.current());
+			holder.replace(state);
+		}
+	}
+	Ok(MutexGuard::map(holder, |it| it.as_mut().unwrap()))
+}
+
+/// Save the state, then carry out the effects decided on by the [`CountingState`]. The state is unlocked before any
+/// request goes out, and an effect that fails is logged without holding up the ones after it.
+async fn run_effects(
+	client: &Client,
+	cache: &HeliosCache,
+	state: MappedMutexGuard<'static, CountingState>,
+	effects: Vec<Effect>,
+) -> eyre::Result<()> {
+	state.strikes.save().await?;
+	drop(state);
+	for effect in effects {
+		if let Err(error) = run_effect(client, cache, &effect).await {
+			tracing::warn!("Could not carry out {effect:?}: {error:?}");
+		}
+	}
+	Ok(())
+}
+
+async fn run_effect(client: &Client, cache: &HeliosCache, effect: &Effect) -> eyre::Result<()> {
+	match effect {
+		Effect::Delete(message_id) => {
+			client.delete_message(COUNTING_CHANNEL, *message_id).await?;
+		}
+		Effect::React(message_id, emoji) => {
+			client
+				.create_reaction(
+					COUNTING_CHANNEL,
+					*message_id,
+					&RequestReactionType::Unicode { name: emoji },
+				)
+				.await?;
+		}
+		Effect::Announce(message) => {
+			client
+				.create_message(COUNTING_CHANNEL)
+				.content(message)
+				.await?;
+		}
+		Effect::Timeout(user, duration) => mute(client, *user, *duration).await?,
+		Effect::UpdateLadder(user, count) => update_ladder(client, cache, *user, *count).await?,
+	}
+	Ok(())
+}
+
+/// Give a user the `counting: N` role matching their highest count, and take away all other tiers.
+async fn update_ladder(
+	client: &Client,
+	cache: &HeliosCache,
+	user: Id<UserMarker>,
+	count: u64,
+) -> eyre::Result<()> {
+	let counting_role = upsert_vanity_role(
+		client,
+		cache,
+		format!("counting: {}", next_power_of_ten(count)).into(),
+	)
+	.await;
+
+	let roles = match cache.member(FIRMAMENT_SERVER, user) {
+		Some(member) => member.roles().to_vec(),
+		None => vec![],
+	};
+	if !roles.contains(&counting_role) {
+		client
+			.add_guild_member_role(FIRMAMENT_SERVER, user, counting_role)
+			.await?;
+	}
+	for role in roles {
+		if role != counting_role && is_ladder_role(cache, role) {
+			client
+				.remove_guild_member_role(FIRMAMENT_SERVER, user, role)
+				.await?;
+		}
+	}
+	Ok(())
+}
+
+fn is_ladder_role(cache: &HeliosCache, role: Id<RoleMarker>) -> bool {
+	cache.role(role).is_some_and(|role| {
+		role.permissions.is_empty()
+			&& role
+				.name
+				.strip_prefix("counting: ")
+				.is_some_and(|tier| tier.parse::<u64>().is_ok())
+	})
+}
+
+const fn next_power_of_ten(c: u64) -> u64 {
+	if c == 0 {
+		return 0;
+	}
+	let mut p = 1;
+	while p < c {
+		p *= 10;
+	}
+	p
+}
+
+fn unix_now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap()
+		.as_secs()
+}
+
+async fn mute(client: &Client, id: Id<UserMarker>, duration: Duration) -> eyre::Result<()> {
+	let mute_until = Timestamp::from_secs((unix_now() + duration.as_secs()) as i64)?;
+	client
+		.update_guild_member(FIRMAMENT_SERVER, id)
+		.communication_disabled_until(Some(mute_until))
+		.await?;
+	Ok(())
+}
+
+/// Replay the whole channel history and report every count that should have been punished.
+/// If `apply` is set, the stored count and streaks get replaced with the replayed ones.
+async fn audit(event: EventWithContext<&MessageCreate>, apply: bool) -> eyre::Result<()> {
+	const MAX_LISTED: usize = 10;
+	event
+		.reply()
+		.content("Replaying the channel history. This may take a while.")
+		.await?;
+	let history = history::fetch_history(&event.client).await?;
+	let replay = history::replay(history.iter().map(history::Posted::from));
+
+	let mut message = format!(
+		"Replayed {} messages and found {} violations.\n",
+		history.len(),
+		replay.violations.len()
+	);
+	for violation in replay.violations.iter().rev().take(MAX_LISTED) {
+		message += &format!(
+			"- https://discord.com/channels/{}/{}/{} by <@{}>: {}\n",
+			FIRMAMENT_SERVER,
+			COUNTING_CHANNEL,
+			violation.message_id,
+			violation.user,
+			violation.kind
+		);
+	}
+	if replay.violations.len() > MAX_LISTED {
+		message += &format!(
+			"-# and {} older violations\n",
+			replay.violations.len() - MAX_LISTED
+		);
+	}
+	match replay.recent.back() {
+		Some(current) => {
+			message += &format!(
+				"The true current count is {} by <@{}>.",
+				current.count, current.user
+			)
+		}
+		None => message += "Nobody has counted yet.",
+	}
+
+	if apply && !replay.recent.is_empty() {
+		let mut state = counting_state(&event.client, None).await?;
+		state.recent = replay.recent;
+		for (user, replayed) in replay.strikes.users() {
+			let record = state.strikes.user(user);
+			record.streak = replayed.streak;
+			record.highest = record.highest.max(replayed.highest);
+		}
+		state.strikes.save().await?;
+		message += " Stored count and streaks have been rewritten.";
+	}
+
+	event.reply().content(&message).await?;
+	Ok(())
+}
+
+async fn on_count_command(event: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
+	let Some(subcommand) = event.content.strip_prefix("!count ") else {
+		return Ok(());
+	};
+	if let Some(rest) = subcommand.strip_prefix("strikes ") {
+		let Some((user, _)) = args::chomp_user(rest) else {
+			event.reply().content("use: !count strikes <@user>").await?;
+			return Ok(());
+		};
+		let mut state = counting_state(&event.client, None).await?;
+		let record = state.strikes.user(user);
+		record.decay(unix_now());
+		let record = record.clone();
+		drop(state);
+		let mut message = format!(
+			"<@{user}> has {} active strikes, a streak of {} and {} saves.",
+			record.strikes, record.streak, record.saves
+		);
+		if let Some(next_decay) = record.next_decay() {
+			message += &format!(" Their next strike expires <t:{next_decay}:R>.");
+		}
+		event.reply().content(&message).await?;
+		return Ok(());
+	}
+	if let Some(rest) = subcommand.strip_prefix("pardon ") {
+		let Some((user, amount)) = args::chomp_user(rest) else {
+			event
+				.reply()
+				.content("use: !count pardon <@user> [amount]")
+				.await?;
+			return Ok(());
+		};
+		let amount = if amount.is_empty() {
+			u32::MAX
+		} else {
+			match amount.parse() {
+				Ok(amount) => amount,
+				Err(_) => {
+					event.reply().content("amount must be a number").await?;
+					return Ok(());
+				}
+			}
+		};
+		let mut state = counting_state(&event.client, None).await?;
+		let record = state.strikes.user(user);
+		let removed = record.pardon(amount, unix_now());
+		let left = record.strikes;
+		state.strikes.save().await?;
+		drop(state);
+		if left == 0 {
+			event
+				.client
+				.update_guild_member(FIRMAMENT_SERVER, user)
+				.communication_disabled_until(None)
+				.await?;
+		}
+		let message = format!("Pardoned {removed} strikes of <@{user}>, {left} left.");
+		event.reply().content(&message).await?;
+		return Ok(());
+	}
+	if subcommand == "ladder" {
+		let highest = counting_state(&event.client, None)
+			.await?
+			.strikes
+			.users()
+			.filter(|(_, record)| record.highest > 0)
+			.map(|(user, record)| (user, record.highest))
+			.collect::<Vec<_>>();
+		let mut failed = 0;
+		for &(user, highest) in &highest {
+			if let Err(err) = update_ladder(&event.client, &event.cache, user, highest).await {
+				tracing::warn!(?err, "Failed to update counting roles of {user}");
+				failed += 1;
+			}
+		}
+		let mut message = format!(
+			"Updated counting roles of {} members ({} failed).",
+			highest.len() - failed,
+			failed
+		);
+		if highest.is_empty() {
+			message += " No highest counts are stored yet, `!count audit apply` can fill them in from the channel history.";
+		}
+		event.reply().content(&message).await?;
+		return Ok(());
+	}
+	if subcommand == "audit" || subcommand == "audit apply" {
+		return audit(event, subcommand == "audit apply").await;
+	}
+	event
+		.reply()
+		.content("unknown subcommand. valid options are strikes, pardon, ladder, audit")
+		.await?;
+	Ok(())
+}
+
+fn parse_count(
+	user: Id<UserMarker>,
+	message_id: Id<MessageMarker>,
+	content: &str,
+) -> Option<LastNumber> {
+	let (number, number_format) = content.split_whitespace().next().and_then(parse_number)?;
+	Some(LastNumber {
+		user,
+		count: number,
+		number_format,
+		message_id,
+		reposted: false,
+	})
+}
+
+fn parse_number(text: &str) -> Option<(u64, NumberFormat)> {
+	if let Some(hex) = text.strip_prefix("0x") {
+		Some((
+			u64::from_str_radix(hex, 16).ok()?,
+			NumberFormat::Hexadecimal,
+		))
+	} else if let Some(hex) = text.strip_suffix("h") {
+		Some((
+			u64::from_str_radix(hex, 16).ok()?,
+			NumberFormat::Hexadecimal,
+		))
+	} else if let Some(oct) = text.strip_prefix("0o") {
+		Some((u64::from_str_radix(oct, 8).ok()?, NumberFormat::Octal))
+	} else if let Some(binary) = text.strip_prefix("0b") {
+		Some((u64::from_str_radix(binary, 2).ok()?, NumberFormat::Binary))
+	} else if let Some(unary) = text.strip_prefix("0u") {
+		if unary.chars().any(|it| it != '0') {
+			None
+		} else {
+			Some((unary.len() as u64, NumberFormat::Unary))
+		}
+	} else {
+		Some((u64::from_str_radix(text, 10).ok()?, NumberFormat::Decimal))
+	}
+}
+
+#[derive(Clone, Debug)]
+struct LastNumber {
+	user: Id<UserMarker>,
+	count: u64,
+	message_id: Id<MessageMarker>,
+	number_format: NumberFormat,
+	/// Whether `message_id` is a repost by the bot rather than the message `user` sent.
+	reposted: bool,
+}
+
+#[derive(Clone, Debug, Copy, Eq, PartialEq)]
+enum NumberFormat {
+	Decimal,
+	Binary,
+	Hexadecimal,
+	Unary,
+	Octal,
+}
+
+/// How many correct counts before the current one are remembered, so edits to them can be detected.
+const RECENT_COUNT_LIMIT: usize = 100;
+
+#[cfg(test)]
+mod tests {
+	use crate::features::counting::{NumberFormat, parse_number};
+
+	#[test]
+	fn test_basic_number_parser() {
+		assert_eq!(parse_number("1"), Some((1, NumberFormat::Decimal)));
+		assert_eq!(parse_number("0b10"), Some((2, NumberFormat::Binary)));
+		assert_eq!(
+			parse_number("0x10"),
+			Some((0x10, NumberFormat::Hexadecimal))
+		);
+		assert_eq!(parse_number("0o10"), Some((8, NumberFormat::Octal)));
+		assert_eq!(parse_number("0u000"), Some((3, NumberFormat::Unary)));
+	}
+}
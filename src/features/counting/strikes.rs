@@ -0,0 +1,200 @@
+use std::{
+	collections::{HashMap, VecDeque},
+	time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use twilight_model::id::{
+	Id,
+	marker::{MessageMarker, UserMarker},
+};
+
+use crate::utils::persist;
+
+const STRIKES_PATH: &str = "counting/strikes.json";
+
+/// Time after which a single strike is forgiven again.
+pub const STRIKE_DECAY: Duration = Duration::from_days(7);
+/// Penalty for the n-th active strike. Any strike past the end of this list gets the last penalty.
+const PENALTIES: &[Penalty] = &[
+	Penalty::Warning,
+	Penalty::Timeout(Duration::from_mins(10)),
+	Penalty::Timeout(Duration::from_hours(1)),
+	Penalty::Timeout(Duration::from_days(1)),
+	Penalty::Timeout(Duration::from_days(7)),
+];
+/// Correct counts in a row needed to earn a save.
+pub const STREAK_PER_SAVE: u32 = 50;
+pub const MAX_SAVES: u32 = 2;
+/// How many penalized messages are remembered. Edits and deletions are only tracked for recent counts, so this only
+/// needs to reach back about as far.
+const PENALIZED_MEMORY: usize = 256;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Offense {
+	/// Wrong number, not a number, or counting twice in a row. Honest mistakes can be covered by a save.
+	Mistake,
+	/// Deleting your own count. Always deliberate, so it counts double and can't be saved.
+	Deletion,
+	/// Editing your own count into something else. Just as deliberate as deleting it.
+	Edit,
+}
+
+impl Offense {
+	const fn weight(self) -> u32 {
+		match self {
+			Offense::Mistake => 1,
+			Offense::Deletion | Offense::Edit => 2,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Penalty {
+	/// A save was used up instead of handing out a strike.
+	Saved,
+	Warning,
+	Timeout(Duration),
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StrikeRecord {
+	pub strikes: u32,
+	/// Unix timestamp of the last strike, or of the last time a strike decayed.
+	pub last_strike: u64,
+	pub streak: u32,
+	pub saves: u32,
+	/// The highest correct count of the user, which decides their ladder role.
+	#[serde(default)]
+	pub highest: u64,
+}
+
+impl StrikeRecord {
+	pub const fn decay(&mut self, now: u64) {
+		let decay = STRIKE_DECAY.as_secs();
+		while self.strikes > 0 && now.saturating_sub(self.last_strike) >= decay {
+			self.strikes -= 1;
+			self.last_strike += decay;
+		}
+	}
+
+	/// When the next active strike will be forgiven, if there is any.
+	pub const fn next_decay(&self) -> Option<u64> {
+		if self.strikes == 0 {
+			None
+		} else {
+			Some(self.last_strike + STRIKE_DECAY.as_secs())
+		}
+	}
+
+	/// Record a correct count. Returns true if this earned a new save.
+	pub const fn record_count(&mut self) -> bool {
+		self.streak += 1;
+		if self.streak % STREAK_PER_SAVE == 0 && self.saves < MAX_SAVES {
+			self.saves += 1;
+			return true;
+		}
+		false
+	}
+
+	pub fn offend(&mut self, offense: Offense, now: u64) -> Penalty {
+		self.decay(now);
+		self.streak = 0;
+		if offense == Offense::Mistake && self.saves > 0 {
+			self.saves -= 1;
+			return Penalty::Saved;
+		}
+		self.strikes += offense.weight();
+		self.last_strike = now;
+		let index = (self.strikes as usize - 1).min(PENALTIES.len() - 1);
+		PENALTIES[index]
+	}
+
+	/// Forgive up to `amount` strikes, returning how many were actually removed.
+	pub fn pardon(&mut self, amount: u32, now: u64) -> u32 {
+		self.decay(now);
+		let removed = amount.min(self.strikes);
+		self.strikes -= removed;
+		removed
+	}
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct StrikeBook {
+	users: HashMap<Id<UserMarker>, StrikeRecord>,
+	/// Messages that already got someone a strike, oldest first.
+	#[serde(default)]
+	penalized: VecDeque<Id<MessageMarker>>,
+}
+
+impl StrikeBook {
+	pub async fn load() -> eyre::Result<StrikeBook> {
+		persist::load_json(STRIKES_PATH).await
+	}
+
+	pub async fn save(&self) -> eyre::Result<()> {
+		persist::save_json(STRIKES_PATH, self).await
+	}
+
+	pub fn user(&mut self, user: Id<UserMarker>) -> &mut StrikeRecord {
+		self.users.entry(user).or_default()
+	}
+
+	pub fn users(&self) -> impl Iterator<Item = (Id<UserMarker>, &StrikeRecord)> {
+		self.users.iter().map(|(user, record)| (*user, record))
+	}
+
+	/// Remember that a message got its author a strike. Returns false if it already had, so editing a count and then
+	/// deleting it is only punished once.
+	pub fn mark_penalized(&mut self, message_id: Id<MessageMarker>) -> bool {
+		if self.penalized.contains(&message_id) {
+			return false;
+		}
+		self.penalized.push_back(message_id);
+		if self.penalized.len() > PENALIZED_MEMORY {
+			self.penalized.pop_front();
+		}
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use crate::features::counting::strikes::{
+		Offense, Penalty, STREAK_PER_SAVE, STRIKE_DECAY, StrikeRecord,
+	};
+
+	#[test]
+	fn test_strikes_escalate_and_decay() {
+		let mut record = StrikeRecord::default();
+		assert_eq!(record.offend(Offense::Mistake, 0), Penalty::Warning);
+		assert_eq!(
+			record.offend(Offense::Mistake, 10),
+			Penalty::Timeout(Duration::from_mins(10))
+		);
+		record.decay(10 + STRIKE_DECAY.as_secs());
+		assert_eq!(record.strikes, 1);
+		assert_eq!(
+			record.offend(Offense::Deletion, 10 + STRIKE_DECAY.as_secs()),
+			Penalty::Timeout(Duration::from_hours(1))
+		);
+	}
+
+	#[test]
+	fn test_saves_cover_mistakes_only() {
+		let mut record = StrikeRecord::default();
+		for _ in 0..STREAK_PER_SAVE - 1 {
+			assert!(!record.record_count());
+		}
+		assert!(record.record_count());
+		assert_eq!(record.offend(Offense::Mistake, 0), Penalty::Saved);
+		assert_eq!(record.strikes, 0);
+		assert_eq!(record.streak, 0);
+		assert_eq!(
+			record.offend(Offense::Deletion, 0),
+			Penalty::Timeout(Duration::from_mins(10))
+		);
+	}
+}
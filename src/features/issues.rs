@@ -33,7 +33,7 @@ async fn on_issue(event: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
 			.title(format!("#{} - {}", issue.number, issue.title))
 			.url(issue.html_url)
 			.timestamp(Timestamp::from_secs(issue.created_at.timestamp())?);
-		if let Some(_) = issue.pull_request {
+		if issue.pull_request.is_some() {
 			embed = embed.description(body).color(match issue.state {
 				IssueState::Open => 0x3040ff,
 				IssueState::Closed => 0xff40e0,
@@ -59,7 +59,7 @@ async fn on_issue(event: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
 
 	Ok(())
 }
-fn parse_md_sections<'a>(source: &'a str) -> HashMap<&'a str, &'a str> {
+fn parse_md_sections(source: &str) -> HashMap<&str, &str> {
 	fixed_regex!(HEADER = "#+ *([^\n]+)\n((?:[^\n]|\n+[^\n#])+)");
 	let mut map = HashMap::new();
 	for m in HEADER.captures_iter(source) {
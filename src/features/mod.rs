@@ -2,5 +2,5 @@ mod badge;
 mod counting;
 mod forward_dms;
 mod issues;
-mod tags;
+pub mod tags;
 mod time;
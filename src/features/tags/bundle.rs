@@ -0,0 +1,335 @@
+use std::{collections::HashMap, fmt::Display, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use eyre::Context as _;
+use serde::{Deserialize, Serialize};
+use twilight_model::{
+	channel::message::AllowedMentions,
+	gateway::payload::incoming::MessageCreate,
+	http::attachment::Attachment,
+	id::{Id, marker::UserMarker},
+};
+
+use super::{TagHandler, name::validate_tag_name, tag::Tag, tag_handler};
+use crate::{EventWithContext, handle_message, utils::cached};
+
+handle_message!(should_obey, on_message_export_tags);
+handle_message!(should_obey, on_message_import_tags);
+
+const BUNDLE_FORMAT: u32 = 1;
+/// Larger uploads are certainly not tag bundles.
+const MAX_BUNDLE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// All tags in a single JSON file, for moving them between bots.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bundle {
+	pub format: u32,
+	pub exported: DateTime<Utc>,
+	pub tags: Vec<BundledTag>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundledTag {
+	pub name: String,
+	/// The complete tag file, including the front matter.
+	pub file: String,
+}
+
+impl Bundle {
+	pub fn new<'a>(tags: impl IntoIterator<Item = &'a Tag>) -> eyre::Result<Bundle> {
+		let mut tags = tags
+			.into_iter()
+			.map(|tag| {
+				Ok(BundledTag {
+					name: tag.name.to_string(),
+					file: tag.to_file_content()?,
+				})
+			})
+			.collect::<eyre::Result<Vec<_>>>()?;
+		tags.sort_by(|a, b| a.name.cmp(&b.name));
+		Ok(Bundle {
+			format: BUNDLE_FORMAT,
+			exported: Utc::now(),
+			tags,
+		})
+	}
+
+	pub fn parse(json: &str) -> eyre::Result<Bundle> {
+		let bundle: Bundle = serde_json::from_str(json).wrap_err("not a valid tag bundle")?;
+		if bundle.format != BUNDLE_FORMAT {
+			eyre::bail!("unsupported tag bundle format {}", bundle.format);
+		}
+		Ok(bundle)
+	}
+}
+
+/// What an import would change. Conflicting tags are only written when overwriting.
+#[derive(Debug, Default)]
+pub struct ImportPlan {
+	pub writes: Vec<Tag>,
+	pub report: ImportReport,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+	pub added: Vec<String>,
+	pub overwritten: Vec<String>,
+	pub unchanged: Vec<String>,
+	/// Tags that differ from an existing tag and were skipped, with the reason.
+	pub conflicts: Vec<(String, String)>,
+	/// Tags that could not be imported at all, with the reason.
+	pub invalid: Vec<(String, String)>,
+}
+
+/// Whether two tags only differ in who edited them when.
+fn same_tag(a: &Tag, b: &Tag) -> bool {
+	let strip = |tag: &Tag| {
+		let mut meta = tag.meta.clone();
+		meta.author = None;
+		meta.last_editor = None;
+		meta.created = None;
+		meta.updated = None;
+		meta
+	};
+	a.content == b.content && strip(a) == strip(b)
+}
+
+fn parse_bundled(bundled: &BundledTag) -> eyre::Result<Tag> {
+	validate_tag_name(&bundled.name)?;
+	let tag = Tag::parse(&bundled.name, &bundled.file)?;
+	for alias in &tag.meta.aliases {
+		validate_tag_name(alias).wrap_err_with(|| format!("invalid alias `{alias}`"))?;
+	}
+	Ok(tag)
+}
+
+/// Merge a bundle into the existing tags.
+pub fn plan_import(
+	existing: impl IntoIterator<Item = Arc<Tag>>,
+	bundle: &Bundle,
+	overwrite: bool,
+) -> ImportPlan {
+	let existing = existing
+		.into_iter()
+		.map(|tag| (tag.name.to_string(), tag))
+		.collect::<HashMap<_, _>>();
+	// Every invocable name, mapped to the tag that owns it
+	let mut owners = HashMap::new();
+	for tag in existing.values() {
+		for name in std::iter::once(&*tag.name).chain(tag.meta.aliases.iter().map(String::as_str)) {
+			owners.insert(name.to_ascii_lowercase(), tag.name.to_string());
+		}
+	}
+
+	let mut plan = ImportPlan::default();
+	for bundled in &bundle.tags {
+		let name = bundled.name.clone();
+		let tag = match parse_bundled(bundled) {
+			Ok(tag) => tag,
+			Err(err) => {
+				plan.report.invalid.push((name, format!("{err:#}")));
+				continue;
+			}
+		};
+		// Scoped tags are meant to share aliases with other tags
+		let shared_aliases = if tag.meta.scope.is_some() {
+			&[][..]
+		} else {
+			&tag.meta.aliases[..]
+		};
+		let clash = std::iter::once(&name).chain(shared_aliases).find_map(|it| {
+			owners
+				.get(&it.to_ascii_lowercase())
+				.filter(|owner| **owner != name)
+				.map(|owner| format!("`{it}` is already used by `{owner}`"))
+		});
+		if let Some(clash) = clash {
+			plan.report.conflicts.push((name, clash));
+			continue;
+		}
+		match existing.get(&name) {
+			Some(old) if same_tag(old, &tag) => {
+				plan.report.unchanged.push(name);
+				continue;
+			}
+			Some(_) if !overwrite => {
+				let reason = "differs from the existing tag".to_owned();
+				plan.report.conflicts.push((name, reason));
+				continue;
+			}
+			Some(_) => plan.report.overwritten.push(name.clone()),
+			None => plan.report.added.push(name.clone()),
+		}
+		for it in std::iter::once(&name).chain(&tag.meta.aliases) {
+			owners.insert(it.to_ascii_lowercase(), name.clone());
+		}
+		plan.writes.push(tag);
+	}
+	plan
+}
+
+/// Import a bundle, recording every written tag in its history like any other edit.
+pub async fn import(
+	handler: &TagHandler,
+	bundle: &Bundle,
+	overwrite: bool,
+	editor: Option<Id<UserMarker>>,
+) -> eyre::Result<ImportPlan> {
+	let plan = plan_import(handler.tags.values(), bundle, overwrite);
+	for tag in &plan.writes {
+		handler.store(&tag.name, Some(tag.clone()), editor).await?;
+	}
+	Ok(plan)
+}
+
+impl Display for ImportReport {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let names = |names: &[String]| {
+			names
+				.iter()
+				.map(|it| format!("`{it}`"))
+				.intersperse(", ".to_owned())
+				.collect::<String>()
+		};
+		writeln!(
+			f,
+			"{} added, {} overwritten, {} unchanged, {} conflicts, {} invalid",
+			self.added.len(),
+			self.overwritten.len(),
+			self.unchanged.len(),
+			self.conflicts.len(),
+			self.invalid.len()
+		)?;
+		if !self.added.is_empty() {
+			writeln!(f, "added: {}", names(&self.added))?;
+		}
+		if !self.overwritten.is_empty() {
+			writeln!(f, "overwritten: {}", names(&self.overwritten))?;
+		}
+		for (name, reason) in &self.conflicts {
+			writeln!(f, "conflict `{name}`: {reason}")?;
+		}
+		for (name, reason) in &self.invalid {
+			writeln!(f, "invalid `{name}`: {reason}")?;
+		}
+		Ok(())
+	}
+}
+
+async fn on_message_export_tags(context: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
+	if context.content.trim() != "!tag export" {
+		return Ok(());
+	}
+	let handler = tag_handler().await;
+	let tags = handler.tags.values().collect::<Vec<_>>();
+	let bundle = Bundle::new(tags.iter().map(|it| &**it))?;
+	let json = serde_json::to_vec_pretty(&bundle)?;
+	let file_name = format!("tags-{}.json", bundle.exported.format("%Y-%m-%d"));
+	let text = format!("exported {} tags", bundle.tags.len());
+	context
+		.reply()
+		.content(&text)
+		.attachments(&[Attachment::from_bytes(file_name, json, 0)])
+		.await?;
+	Ok(())
+}
+
+async fn on_message_import_tags(context: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
+	let Some(mode) = context.content.trim().strip_prefix("!tag import") else {
+		return Ok(());
+	};
+	let overwrite = match mode.trim() {
+		"" => false,
+		"overwrite" => true,
+		_ => {
+			context
+				.reply()
+				.content("use: !tag import [overwrite], with a tag bundle attached")
+				.await?;
+			return Ok(());
+		}
+	};
+	let Some(attachment) = context
+		.attachments
+		.first()
+		.filter(|it| it.filename.ends_with(".json") && it.size <= MAX_BUNDLE_SIZE)
+	else {
+		context
+			.reply()
+			.content("please attach a tag bundle from `!tag export`")
+			.await?;
+		return Ok(());
+	};
+	let path = cached::download_url(attachment.url.clone()).await?;
+	let bundle = match Bundle::parse(&tokio::fs::read_to_string(path).await?) {
+		Ok(bundle) => bundle,
+		Err(err) => {
+			let text = format!("could not read that bundle: {err:#}");
+			context.reply().content(&text).await?;
+			return Ok(());
+		}
+	};
+
+	let handler = tag_handler().await;
+	let plan = import(&handler, &bundle, overwrite, Some(context.author.id)).await?;
+	let mut text = plan.report.to_string();
+	if !overwrite && !plan.report.conflicts.is_empty() {
+		text += "-# use `!tag import overwrite` to replace differing tags\n";
+	}
+	let text = text.chars().take(2000).collect::<String>();
+	context
+		.reply()
+		.content(&text)
+		.allowed_mentions(Some(&AllowedMentions::default()))
+		.await?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use crate::features::tags::{
+		bundle::{Bundle, plan_import},
+		tag::Tag,
+	};
+
+	#[test]
+	fn test_import_conflicts() {
+		let existing = [
+			Tag::parse("faq", "Read the docs").unwrap(),
+			Tag::parse("install", "+++\naliases = [\"setup\"]\n+++\nDownload it").unwrap(),
+		];
+		let incoming = [
+			Tag::parse("faq", "+++\nauthor = \"1\"\n+++\nRead the docs").unwrap(),
+			Tag::parse("install", "Download it from modrinth").unwrap(),
+			Tag::parse("Setup", "Something else").unwrap(),
+			Tag::parse("new", "Brand new").unwrap(),
+		];
+		let mut bundle = Bundle::new(&incoming).unwrap();
+		let json = serde_json::to_string(&bundle).unwrap();
+		bundle = Bundle::parse(&json).unwrap();
+		bundle.tags.push(crate::features::tags::bundle::BundledTag {
+			name: "../evil".to_owned(),
+			file: String::new(),
+		});
+		let existing = existing.map(Arc::new);
+
+		let plan = plan_import(existing.clone(), &bundle, false);
+		assert_eq!(plan.report.added, ["new"]);
+		assert_eq!(plan.report.unchanged, ["faq"]);
+		let conflicts = plan
+			.report
+			.conflicts
+			.iter()
+			.map(|(name, _)| name.as_str())
+			.collect::<Vec<_>>();
+		assert_eq!(conflicts, ["Setup", "install"]);
+		assert_eq!(plan.report.invalid.len(), 1);
+		assert_eq!(plan.writes.len(), 1);
+
+		let plan = plan_import(existing, &bundle, true);
+		assert_eq!(plan.report.overwritten, ["install"]);
+		assert_eq!(plan.writes.len(), 2);
+	}
+}
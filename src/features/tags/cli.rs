@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use super::{
+	TagHandler,
+	bundle::{self, Bundle},
+};
+
+const USAGE: &str =
+	"usage: helios tag export <bundle.json>\n       helios tag import <bundle.json> [--overwrite]";
+
+/// `helios tag ...`, for moving tags between bots without going through Discord. Works on the `tags` folder in the
+/// current directory, like the bot itself.
+pub async fn run(args: &[String]) -> eyre::Result<()> {
+	let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+	match args.as_slice() {
+		["export", bundle_path] => export(Path::new(bundle_path)).await,
+		["import", bundle_path] => import(Path::new(bundle_path), false).await,
+		["import", bundle_path, "--overwrite"] => import(Path::new(bundle_path), true).await,
+		_ => eyre::bail!("{USAGE}"),
+	}
+}
+
+async fn load_tags() -> eyre::Result<TagHandler> {
+	let handler = TagHandler::load().await?;
+	for error in handler.load_errors.lock().await.iter() {
+		eprintln!("skipping broken tag file {error}");
+	}
+	Ok(handler)
+}
+
+async fn export(bundle_path: &Path) -> eyre::Result<()> {
+	let handler = load_tags().await?;
+	let tags = handler.tags.values().collect::<Vec<_>>();
+	let bundle = Bundle::new(tags.iter().map(|it| &**it))?;
+	tokio::fs::write(bundle_path, serde_json::to_vec_pretty(&bundle)?).await?;
+	println!(
+		"exported {} tags to {}",
+		bundle.tags.len(),
+		bundle_path.display()
+	);
+	Ok(())
+}
+
+/// Like `!tag import`, but the changes are recorded in the tag history without an author, since there is nobody to
+/// attribute them to.
+async fn import(bundle_path: &Path, overwrite: bool) -> eyre::Result<()> {
+	let handler = load_tags().await?;
+	let bundle = Bundle::parse(&tokio::fs::read_to_string(bundle_path).await?)?;
+	let plan = bundle::import(&handler, &bundle, overwrite, None).await?;
+	print!("{}", plan.report);
+	Ok(())
+}
@@ -0,0 +1,186 @@
+use tokio::sync::OnceCell;
+use twilight_model::{
+	application::interaction::{Interaction, InteractionData},
+	channel::message::{
+		Component, MessageFlags,
+		component::{ActionRow, TextInput, TextInputStyle},
+	},
+	gateway::payload::incoming::{InteractionCreate, Ready},
+	guild::Permissions,
+	http::interaction::{InteractionResponse, InteractionResponseType},
+};
+use twilight_util::builder::InteractionResponseDataBuilder;
+
+use super::{MAX_TAG_NAME_LENGTH, tag_handler, validate_tag_name};
+use crate::{EventWithContext, handle, utils::user_perms};
+
+handle!(Ready, register_save_as_tag);
+handle!(InteractionCreate, on_save_as_tag);
+
+const COMMAND_NAME: &str = "Save as tag";
+const MODAL_ID: &str = "save_as_tag";
+const NAME_INPUT: &str = "name";
+const CONTENT_INPUT: &str = "content";
+/// Discord's limit for text inputs.
+const MAX_INPUT_LENGTH: usize = 4000;
+
+/// Ready comes again after every reconnect, but the command only needs to be registered once. Creating a command
+/// with an existing name updates it, and leaves all other global commands alone.
+async fn register_save_as_tag(event: EventWithContext<&Ready>) -> eyre::Result<()> {
+	static REGISTERED: OnceCell<()> = OnceCell::const_new();
+	REGISTERED
+		.get_or_try_init(|| async {
+			event
+				.client
+				.interaction(event.application.id)
+				.create_global_command()
+				.message(COMMAND_NAME)
+				.default_member_permissions(Permissions::MANAGE_MESSAGES)
+				.await?;
+			eyre::Ok(())
+		})
+		.await?;
+	Ok(())
+}
+
+fn text_input(
+	custom_id: &str,
+	label: &str,
+	style: TextInputStyle,
+	max_length: usize,
+	value: Option<String>,
+) -> Component {
+	Component::ActionRow(ActionRow {
+		components: vec![Component::TextInput(TextInput {
+			custom_id: custom_id.to_owned(),
+			label: label.to_owned(),
+			max_length: Some(max_length as u16),
+			min_length: Some(1),
+			placeholder: None,
+			required: Some(true),
+			style,
+			value,
+		})],
+	})
+}
+
+async fn respond(
+	event: &EventWithContext<&InteractionCreate>,
+	response: InteractionResponse,
+) -> eyre::Result<()> {
+	event
+		.client
+		.interaction(event.application_id)
+		.create_response(event.id, &event.token, &response)
+		.await?;
+	Ok(())
+}
+
+async fn respond_privately(
+	event: &EventWithContext<&InteractionCreate>,
+	text: impl Into<String>,
+) -> eyre::Result<()> {
+	let data = InteractionResponseDataBuilder::new()
+		.content(text)
+		.flags(MessageFlags::EPHEMERAL)
+		.build();
+	let response = InteractionResponse {
+		kind: InteractionResponseType::ChannelMessageWithSource,
+		data: Some(data),
+	};
+	respond(event, response).await
+}
+
+fn is_staff(interaction: &Interaction) -> bool {
+	let roles = interaction.member.as_ref().map(|it| &*it.roles);
+	interaction
+		.author()
+		.is_some_and(|user| user_perms(user, roles).should_obey())
+}
+
+/// Handles both the context menu command, which opens the modal, and the submitted modal.
+async fn on_save_as_tag(event: EventWithContext<&InteractionCreate>) -> eyre::Result<()> {
+	match &event.data {
+		Some(InteractionData::ApplicationCommand(command)) if command.name == COMMAND_NAME => {
+			if !is_staff(&event) {
+				return respond_privately(&event, "only staff can save tags").await;
+			}
+			let message = command
+				.target_id
+				.and_then(|id| command.resolved.as_ref()?.messages.get(&id.cast()));
+			let Some(message) = message else {
+				return respond_privately(&event, "could not find that message").await;
+			};
+			// Attachments are kept as links. Discord renews expired attachment links when they are shown, as long as
+			// the original message is still around.
+			let mut content = message.content.clone();
+			for attachment in &message.attachments {
+				content += "\n";
+				content += &attachment.url;
+			}
+			let content = content
+				.trim()
+				.chars()
+				.take(MAX_INPUT_LENGTH)
+				.collect::<String>();
+			let data = InteractionResponseDataBuilder::new()
+				.custom_id(MODAL_ID)
+				.title(COMMAND_NAME)
+				.components([
+					text_input(
+						NAME_INPUT,
+						"Tag name",
+						TextInputStyle::Short,
+						MAX_TAG_NAME_LENGTH,
+						None,
+					),
+					text_input(
+						CONTENT_INPUT,
+						"Content",
+						TextInputStyle::Paragraph,
+						MAX_INPUT_LENGTH,
+						Some(content),
+					),
+				])
+				.build();
+			let response = InteractionResponse {
+				kind: InteractionResponseType::Modal,
+				data: Some(data),
+			};
+			respond(&event, response).await
+		}
+		Some(InteractionData::ModalSubmit(modal)) if modal.custom_id == MODAL_ID => {
+			if !is_staff(&event) {
+				return respond_privately(&event, "only staff can save tags").await;
+			}
+			let value = |custom_id: &str| {
+				modal
+					.components
+					.iter()
+					.flat_map(|row| &row.components)
+					.find(|it| it.custom_id == custom_id)
+					.and_then(|it| it.value.as_deref())
+					.unwrap_or_default()
+			};
+			let (name, content) = (value(NAME_INPUT).trim(), value(CONTENT_INPUT));
+			if let Err(err) = validate_tag_name(name) {
+				return respond_privately(&event, format!("invalid tag name: {err}")).await;
+			}
+			let Some(author) = event.author_id() else {
+				return Ok(());
+			};
+			let handler = tag_handler().await;
+			if let Some(existing) = handler.conflicting_name(name) {
+				let text = format!("tag `{name}` conflicts with the existing tag `{existing}`");
+				return respond_privately(&event, text).await;
+			}
+			let verb = match handler.get(name) {
+				Some(_) => "updated",
+				None => "created",
+			};
+			handler.write_tag(name, Some(content), author).await?;
+			respond_privately(&event, format!("{verb} tag `{name}`")).await
+		}
+		_ => Ok(()),
+	}
+}
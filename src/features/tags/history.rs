@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use tokio::io::AsyncWriteExt as _;
+use twilight_model::id::{Id, marker::UserMarker};
+
+use super::name::validate_tag_name;
+
+/// One version of a tag file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Revision {
+	/// `None` for the version a tag had before its history was recorded, and for changes made outside of Discord.
+	pub author: Option<Id<UserMarker>>,
+	pub timestamp: DateTime<Utc>,
+	/// The full file content, or `None` if the tag was deleted.
+	pub content: Option<String>,
+}
+
+/// History files live next to the tags folder, so they are never loaded as tags themselves.
+fn history_path(tags_path: &Path, key: &str) -> eyre::Result<PathBuf> {
+	validate_tag_name(key)?;
+	Ok(tags_path
+		.with_file_name("tag_history")
+		.join(format!("{key}.jsonl")))
+}
+
+/// Load all revisions of a tag, oldest first. Revision numbers start at 1.
+pub async fn load(tags_path: &Path, key: &str) -> eyre::Result<Vec<Revision>> {
+	let content = match tokio::fs::read_to_string(history_path(tags_path, key)?).await {
+		Ok(content) => content,
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+		Err(err) => return Err(err.into()),
+	};
+	content
+		.lines()
+		.map(|line| Ok(serde_json::from_str(line)?))
+		.collect()
+}
+
+/// Append a revision to the history of a tag. If the tag existed before its history started, the previous
+/// content is recorded first, so that it can be reverted to.
+pub async fn record(
+	tags_path: &Path,
+	key: &str,
+	previous: Option<&str>,
+	revision: Revision,
+) -> eyre::Result<()> {
+	let path = history_path(tags_path, key)?;
+	if let Some(parent) = path.parent() {
+		tokio::fs::create_dir_all(parent).await?;
+	}
+	let mut lines = String::new();
+	let has_history = tokio::fs::try_exists(&path).await?;
+	if let Some(previous) = previous.filter(|_| !has_history) {
+		let baseline = Revision {
+			author: None,
+			timestamp: revision.timestamp,
+			content: Some(previous.to_owned()),
+		};
+		lines += &serde_json::to_string(&baseline)?;
+		lines += "\n";
+	}
+	lines += &serde_json::to_string(&revision)?;
+	lines += "\n";
+	let mut file = tokio::fs::OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.await?;
+	file.write_all(lines.as_bytes()).await?;
+	Ok(())
+}
+
+/// A unified diff between two versions of a tag file.
+pub fn diff(old: Option<&str>, new: Option<&str>) -> String {
+	TextDiff::from_lines(old.unwrap_or_default(), new.unwrap_or_default())
+		.unified_diff()
+		.context_radius(2)
+		.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::Utc;
+	use twilight_model::id::Id;
+
+	use crate::features::tags::history::{Revision, diff, load, record};
+
+	#[tokio::test]
+	async fn test_history_records_baseline() {
+		let tags_path = std::env::temp_dir()
+			.join(format!("helios-history-{}", std::process::id()))
+			.join("tags");
+		let revision = |content: Option<&str>| Revision {
+			author: Some(Id::new(1)),
+			timestamp: Utc::now(),
+			content: content.map(ToOwned::to_owned),
+		};
+		record(&tags_path, "faq", Some("old\n"), revision(Some("new\n")))
+			.await
+			.unwrap();
+		record(&tags_path, "faq", Some("new\n"), revision(None))
+			.await
+			.unwrap();
+		let revisions = load(&tags_path, "faq").await.unwrap();
+		assert_eq!(revisions.len(), 3);
+		assert_eq!(revisions[0].author, None);
+		assert_eq!(revisions[0].content.as_deref(), Some("old\n"));
+		assert_eq!(revisions[2].content, None);
+		assert!(load(&tags_path, "missing").await.unwrap().is_empty());
+		_ = tokio::fs::remove_dir_all(tags_path.parent().unwrap()).await;
+
+		let diff = diff(Some("a\nb\n"), Some("a\nc\n"));
+		assert!(diff.contains("-b\n") && diff.contains("+c\n"));
+	}
+}
@@ -0,0 +1,683 @@
+use std::{path::PathBuf, sync::Arc};
+
+use cow_hashmap::CowHashMap;
+use tokio::sync::Mutex;
+use twilight_model::{
+	channel::message::AllowedMentions,
+	gateway::payload::incoming::MessageCreate,
+	http::attachment::Attachment,
+	id::{Id, marker::UserMarker},
+};
+
+use self::{
+	history::Revision,
+	name::{MAX_TAG_NAME_LENGTH, tag_file_name, validate_tag_name},
+	scope::TagLocation,
+	tag::Tag,
+	template::TemplateContext,
+	usage::{Usage, UsageStats},
+};
+use crate::{EventWithContext, claim_command, handle_message, utils::args};
+
+mod bundle;
+pub mod cli;
+mod context_menu;
+mod history;
+mod name;
+mod reload;
+mod scope;
+mod search;
+mod suggestions;
+mod tag;
+mod template;
+mod triggers;
+mod usage;
+
+handle_message!(should_reply, on_message_send_tags);
+handle_message!(should_reply, on_message_search_tags);
+handle_message!(should_obey, on_message_edit_tags);
+claim_command!("tag");
+
+/// Discord's limit for the content of a single message.
+const MESSAGE_LIMIT: usize = 2000;
+
+/// How many different tags a single message may trigger. Configured using the `TAG_REPLY_LIMIT` env var.
+fn tag_reply_limit() -> usize {
+	std::env::var("TAG_REPLY_LIMIT")
+		.ok()
+		.and_then(|it| it.parse().ok())
+		.unwrap_or(3)
+}
+
+async fn on_message_send_tags(context: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
+	let message = context.event;
+	let handler = tag_handler().await;
+	let location = TagLocation::of(&context.cache, message);
+	// Embeds are limited to 10 per message
+	let limit = tag_reply_limit().min(10);
+	let mut tags = vec![];
+	let mut skipped = 0;
+	let mut texts = vec![];
+	let mut embeds = vec![];
+	let mut users = vec![message.author.id];
+	for trigger in triggers::find_triggers(&message.content) {
+		let Some(tag) = handler.resolve(trigger.name, &location) else {
+			continue;
+		};
+		if tags.iter().any(|it: &Arc<Tag>| it.name == tag.name) {
+			continue;
+		}
+		if tags.len() >= limit {
+			skipped += 1;
+			continue;
+		}
+		let user = match args::chomp_user(trigger.args) {
+			Some((user, _)) => user,
+			None => match &message.referenced_message {
+				Some(referenced) => referenced.author.id,
+				None => message.author.id,
+			},
+		};
+		let template_context = TemplateContext {
+			author: message.author.id,
+			user,
+			channel: message.channel_id,
+			args: trigger.args,
+		};
+		let content = template::render(&tag.content, &template_context).await;
+		match tag.embed(&content)? {
+			Some(embed) => embeds.push(embed),
+			None => texts.push(content),
+		}
+		users.push(user);
+		tags.push(tag);
+	}
+	if tags.is_empty() {
+		let word = message.content.trim().strip_prefix('!');
+		let suggestion = word
+			.filter(|it| !it.is_empty() && !it.contains(char::is_whitespace))
+			.and_then(|it| handler.suggest(it, &location));
+		if let Some(suggestion) = suggestion {
+			let text = format!("No tag with that name. Did you mean `!{suggestion}`?");
+			context.reply().content(&text).await?;
+		}
+		return Ok(());
+	}
+	if skipped > 0 {
+		texts.push(format!(
+			"-# {skipped} more tags were not sent, at most {limit} are sent per message"
+		));
+	}
+
+	users.sort();
+	users.dedup();
+	let allowed_mentions = AllowedMentions {
+		users,
+		replied_user: true,
+		..AllowedMentions::default()
+	};
+	let mut pages = triggers::paginate(&texts.join("\n\n"), MESSAGE_LIMIT).into_iter();
+	// The embeds go along with the first page
+	let first_page = pages.next();
+	let reply = context.reply().allowed_mentions(Some(&allowed_mentions));
+	match &first_page {
+		Some(page) => reply.content(page).embeds(&embeds).await?,
+		None => reply.embeds(&embeds).await?,
+	};
+	for page in pages {
+		context
+			.reply()
+			.allowed_mentions(Some(&allowed_mentions))
+			.content(&page)
+			.await?;
+	}
+
+	for tag in tags {
+		let usage = Usage {
+			timestamp: chrono::Utc::now(),
+			tag: tag.name.to_string(),
+			channel: message.channel_id,
+			user: message.author.id,
+		};
+		if let Err(err) = usage::record(&usage).await {
+			tracing::warn!(?err, "Failed to record tag usage");
+		}
+	}
+	Ok(())
+}
+
+async fn on_message_search_tags(context: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
+	const MAX_RESULTS: usize = 10;
+	let Some(query) = context.content.strip_prefix("!tag search ") else {
+		return Ok(());
+	};
+	let handler = tag_handler().await;
+	let location = TagLocation::of(&context.cache, context.event);
+	let results = handler.search(query, &location);
+	if results.is_empty() {
+		context.reply().content("No tags found.").await?;
+		return Ok(());
+	}
+	let mut text = results
+		.iter()
+		.take(MAX_RESULTS)
+		.map(|tag| {
+			let summary = match &tag.meta.description {
+				Some(description) => description.as_str(),
+				None => tag.content.lines().next().unwrap_or_default(),
+			};
+			format!(
+				"`!{}` — {}",
+				tag.name,
+				summary.chars().take(100).collect::<String>()
+			)
+		})
+		.intersperse("\n".to_owned())
+		.collect::<String>();
+	if results.len() > MAX_RESULTS {
+		text += &format!("\n-# and {} more", results.len() - MAX_RESULTS);
+	}
+	context
+		.reply()
+		.content(&text)
+		.allowed_mentions(Some(&AllowedMentions::default()))
+		.await?;
+	Ok(())
+}
+
+async fn on_message_edit_tags(context: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
+	let content = &context.content;
+	let Some(subcommand) = content.strip_prefix("!tag ") else {
+		return Ok(());
+	};
+	let handler = tag_handler().await;
+	if subcommand.starts_with("search ")
+		|| subcommand.starts_with("suggest ")
+		|| subcommand.starts_with("export")
+		|| subcommand.starts_with("import")
+	{
+		// Handled by on_message_search_tags and the suggestions and bundle modules
+		return Ok(());
+	}
+	if subcommand == "list" {
+		// Only the tags that can be used in this channel
+		let location = TagLocation::of(&context.cache, context.event);
+		let mut tags = handler
+			.tags
+			.values()
+			.filter(|tag| tag.specificity(&location).is_some())
+			.collect::<Vec<_>>();
+		tags.sort_by(|a, b| a.name.cmp(&b.name));
+		let mut content = tags
+			.iter()
+			.map(|tag| {
+				let mut line = format!("`{}`", tag.name);
+				if !tag.meta.aliases.is_empty() {
+					let aliases = tag
+						.meta
+						.aliases
+						.iter()
+						.map(|x| format!("`{x}`"))
+						.intersperse(", ".to_owned())
+						.collect::<String>();
+					line += &format!(" ({aliases})");
+				}
+				if let Some(description) = &tag.meta.description {
+					line += &format!(" — {description}");
+				}
+				line
+			})
+			.intersperse("\n".to_owned())
+			.collect::<String>();
+		// Errors can be long, the full ones are in the logs
+		const MAX_LISTED_ERRORS: usize = 3;
+		let load_errors = handler.load_errors.lock().await;
+		if !load_errors.is_empty() {
+			let mut listed = load_errors
+				.iter()
+				.take(MAX_LISTED_ERRORS)
+				.map(|it| it.chars().take(100).collect::<String>())
+				.collect::<Vec<_>>()
+				.join(", ");
+			if load_errors.len() > MAX_LISTED_ERRORS {
+				listed += &format!(" and {} more", load_errors.len() - MAX_LISTED_ERRORS);
+			}
+			content += &format!(
+				"\n-# {} tag files failed to load: {listed}",
+				load_errors.len()
+			);
+		}
+		let pages = triggers::paginate(&content, MESSAGE_LIMIT);
+		if pages.is_empty() {
+			context.reply().content("no tags can be used here").await?;
+		}
+		for page in pages {
+			context.reply().content(&page).await?;
+		}
+		return Ok(());
+	}
+	if subcommand == "stats" {
+		let usages = usage::load().await?;
+		let stats = UsageStats::compute(&usages, handler.tags.keys(), chrono::Utc::now());
+		let text = stats.to_string().chars().take(2000).collect::<String>();
+		context.reply().content(&text).await?;
+		return Ok(());
+	}
+	if subcommand == "stats csv" {
+		let csv = match tokio::fs::read(usage::USAGE_PATH).await {
+			Ok(csv) => csv,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+				context
+					.reply()
+					.content("no tags have been used yet")
+					.await?;
+				return Ok(());
+			}
+			Err(err) => return Err(err.into()),
+		};
+		context
+			.reply()
+			.attachments(&[Attachment::from_bytes("tag_usage.csv".to_owned(), csv, 0)])
+			.await?;
+		return Ok(());
+	}
+	if subcommand == "reload" {
+		let summary = handler.reload().await?;
+		let text = format!("reloaded tags: {summary}");
+		context.reply().content(&text).await?;
+		return Ok(());
+	}
+	if let Some(rest) = subcommand.strip_prefix("add ") {
+		let Some((key, reply)) = rest.split_once(' ') else {
+			context
+				.reply()
+				.content("use: !tag add <name> <content>")
+				.await?;
+			return Ok(());
+		};
+		if !check_tag_name(&context, key).await? {
+			return Ok(());
+		}
+		if let Some(existing) = handler.conflicting_name(key) {
+			let text = format!("tag `{key}` conflicts with the existing tag `{existing}`");
+			context.reply().content(&text).await?;
+			return Ok(());
+		}
+		// An alias edits the tag it belongs to
+		let name = handler.edit_key(key);
+		let verb = match handler.tags.contains_key(&name) {
+			true => "updated",
+			false => "created",
+		};
+		handler
+			.write_tag(&name, Some(reply), context.author.id)
+			.await?;
+		let text = format!("{verb} tag `{name}`{}", alias_note(key, &name));
+		context.reply().content(&text).await?;
+		return Ok(());
+	}
+	if let Some(rest) = subcommand.strip_prefix("del ") {
+		if !check_tag_name(&context, rest).await? {
+			return Ok(());
+		}
+		let name = handler.edit_key(rest);
+		if !handler.tags.contains_key(&name) {
+			let text = format!("there is no tag `{rest}`");
+			context.reply().content(&text).await?;
+			return Ok(());
+		}
+		handler.write_tag(&name, None, context.author.id).await?;
+		let text = format!("deleted tag `{name}`{}", alias_note(rest, &name));
+		context.reply().content(&text).await?;
+		return Ok(());
+	}
+	if let Some(key) = subcommand.strip_prefix("history ") {
+		if !check_tag_name(&context, key).await? {
+			return Ok(());
+		}
+		// History is kept under the tag's own name, aliases lead there too
+		let key: &str = &handler.edit_key(key);
+		let path = handler.write_handle.lock().await.clone();
+		let revisions = history::load(&path, key).await?;
+		if revisions.is_empty() {
+			let text = format!("no history for tag `{key}`");
+			context.reply().content(&text).await?;
+			return Ok(());
+		}
+		let text = revisions
+			.iter()
+			.enumerate()
+			.rev()
+			.take(15)
+			.map(|(index, revision)| {
+				let mut line = format!("`r{}` <t:{}:R>", index + 1, revision.timestamp.timestamp());
+				match revision.author {
+					Some(author) => line += &format!(" by <@{author}>"),
+					None => line += " (made outside of Discord)",
+				}
+				if revision.content.is_none() {
+					line += " — deleted";
+				}
+				line
+			})
+			.intersperse("\n".to_owned())
+			.collect::<String>();
+		context.reply().content(&text).await?;
+		return Ok(());
+	}
+	if let Some(rest) = subcommand.strip_prefix("diff ") {
+		let Some((key, revision)) = parse_revision(rest) else {
+			context
+				.reply()
+				.content("use: !tag diff <name> <revision>")
+				.await?;
+			return Ok(());
+		};
+		if !check_tag_name(&context, key).await? {
+			return Ok(());
+		}
+		// History is kept under the tag's own name, aliases lead there too
+		let key: &str = &handler.edit_key(key);
+		let path = handler.write_handle.lock().await.clone();
+		let revisions = history::load(&path, key).await?;
+		let Some(new) = revision.checked_sub(1).and_then(|it| revisions.get(it)) else {
+			let text = format!("tag `{key}` has no revision {revision}");
+			context.reply().content(&text).await?;
+			return Ok(());
+		};
+		let old = revision
+			.checked_sub(2)
+			.and_then(|it| revisions.get(it))
+			.and_then(|it| it.content.as_deref());
+		let diff = history::diff(old, new.content.as_deref());
+		let diff = diff
+			.chars()
+			.take(1900)
+			.collect::<String>()
+			.replace("```", "`\u{200B}``");
+		let text = format!("```diff\n{diff}\n```");
+		context.reply().content(&text).await?;
+		return Ok(());
+	}
+	if let Some(rest) = subcommand.strip_prefix("revert ") {
+		let Some((key, revision)) = parse_revision(rest) else {
+			context
+				.reply()
+				.content("use: !tag revert <name> <revision>")
+				.await?;
+			return Ok(());
+		};
+		if !check_tag_name(&context, key).await? {
+			return Ok(());
+		}
+		let key: &str = &handler.edit_key(key);
+		let text = if handler.revert(key, revision, context.author.id).await? {
+			format!("reverted tag `{key}` to revision {revision}")
+		} else {
+			format!("tag `{key}` has no revision {revision}")
+		};
+		context.reply().content(&text).await?;
+		return Ok(());
+	}
+	context
+		.reply()
+		.content(
+			"unknown subcommand. valid options are add, list, del, search, suggest, history, diff, revert, export, import, reload, stats",
+		)
+		.await?;
+	Ok(())
+}
+
+async fn tag_handler() -> Arc<TagHandler> {
+	static _TAG_HANDLER: Mutex<Option<Arc<TagHandler>>> = Mutex::const_new(None);
+	let mut opt = _TAG_HANDLER.lock().await;
+	if let Some(handler) = opt.as_ref() {
+		return handler.clone();
+	}
+	let handler = TagHandler::load().await.unwrap();
+	let handler = Arc::new(handler);
+	reload::watch(handler.clone());
+	opt.replace(handler.clone());
+	handler
+}
+
+struct TagHandler {
+	tags: CowHashMap<Arc<str>, Tag>,
+	/// Maps every alias to the names of the tags it points to. Scoped tags may share an alias.
+	aliases: CowHashMap<Arc<str>, Vec<Arc<str>>>,
+	write_handle: Mutex<PathBuf>,
+	/// Descriptions of tag files that could not be loaded.
+	load_errors: Mutex<Vec<String>>,
+}
+impl TagHandler {
+	/// Look up a tag by its name or one of its aliases, regardless of scope.
+	fn get(&self, name: &str) -> Option<Arc<Tag>> {
+		self.tags.get(name).or_else(|| {
+			let targets = self.aliases.get(name)?;
+			self.tags.get(&**targets.first()?)
+		})
+	}
+
+	/// An existing tag whose name only differs in case. Those would end up in the same file on case insensitive file
+	/// systems.
+	fn conflicting_name(&self, name: &str) -> Option<Arc<str>> {
+		self.tags
+			.keys()
+			.find(|it| it.eq_ignore_ascii_case(name) && **it != *name)
+	}
+
+	/// Find the tag a name refers to at a location. Of all tags with that name or alias, the most specifically scoped
+	/// one wins.
+	fn resolve(&self, name: &str, location: &TagLocation) -> Option<Arc<Tag>> {
+		let targets = self.aliases.get(name).unwrap_or_default();
+		let mut best: Option<(u8, Arc<Tag>)> = None;
+		for tag in self
+			.tags
+			.get(name)
+			.into_iter()
+			.chain(targets.iter().filter_map(|it| self.tags.get(&**it)))
+		{
+			let Some(specificity) = tag.specificity(location) else {
+				continue;
+			};
+			if best.as_ref().is_none_or(|(best, _)| specificity > *best) {
+				best = Some((specificity, tag));
+			}
+		}
+		best.map(|(_, tag)| tag)
+	}
+
+	/// The name of the tag file an edit of `name` should go to: the tag itself, or the tag it is an alias of, as long as
+	/// that is unambiguous.
+	fn edit_key(&self, name: &str) -> Arc<str> {
+		if self.tags.contains_key(name) {
+			return name.into();
+		}
+		match self.aliases.get(name).as_deref().map(Vec::as_slice) {
+			Some([target]) => target.clone(),
+			_ => name.into(),
+		}
+	}
+
+	/// Create, update or delete a tag. Updates keep the existing front matter, only bumping the edit metadata.
+	async fn write_tag(
+		&self,
+		key: &str,
+		reply: Option<&str>,
+		editor: Id<UserMarker>,
+	) -> eyre::Result<()> {
+		let key: &str = &self.edit_key(key);
+		let tag = reply.map(|content| {
+			let mut tag = match self.tags.get(key) {
+				Some(old_tag) => (*old_tag).clone(),
+				None => Tag {
+					name: key.into(),
+					meta: Default::default(),
+					content: String::new(),
+				},
+			};
+			tag.content = content.to_owned();
+			tag.meta.author.get_or_insert(editor);
+			tag.meta.created.get_or_insert(chrono::Utc::now());
+			tag
+		});
+		self.store(key, tag, Some(editor)).await
+	}
+
+	/// Restore a tag to how it was at the given revision. This is recorded as a new revision itself.
+	async fn revert(
+		&self,
+		key: &str,
+		revision: usize,
+		editor: Id<UserMarker>,
+	) -> eyre::Result<bool> {
+		let path = self.write_handle.lock().await.clone();
+		let revisions = history::load(&path, key).await?;
+		let Some(revision) = revision.checked_sub(1).and_then(|it| revisions.get(it)) else {
+			return Ok(false);
+		};
+		let tag = revision
+			.content
+			.as_deref()
+			.map(|content| Tag::parse(key, content))
+			.transpose()?;
+		self.store(key, tag, Some(editor)).await?;
+		Ok(true)
+	}
+
+	/// Write a tag to disk and record the change in its history. `None` deletes the tag. Changes made outside of
+	/// Discord have no editor.
+	async fn store(
+		&self,
+		key: &str,
+		tag: Option<Tag>,
+		editor: Option<Id<UserMarker>>,
+	) -> eyre::Result<()> {
+		tracing::info!("Writing {key}");
+		let path = self.write_handle.lock().await;
+		tokio::fs::create_dir_all(&*path).await?;
+		validate_tag_name(key)?;
+		let file_path = path.join(tag_file_name(key));
+		let previous = self
+			.tags
+			.get(key)
+			.map(|it| it.to_file_content())
+			.transpose()?;
+		let now = chrono::Utc::now();
+		let content = match tag {
+			Some(mut tag) => {
+				if editor.is_some() {
+					tag.meta.last_editor = editor;
+				}
+				tag.meta.updated = Some(now);
+				let content = tag.to_file_content()?;
+				tokio::fs::write(file_path, &content).await?;
+				self.insert(tag);
+				Some(content)
+			}
+			None => {
+				tokio::fs::remove_file(file_path).await?;
+				self.remove(key);
+				None
+			}
+		};
+		let revision = Revision {
+			author: editor,
+			timestamp: now,
+			content,
+		};
+		history::record(&path, key, previous.as_deref(), revision).await?;
+		tracing::info!("Tag write of {key} success.");
+		Ok(())
+	}
+
+	fn insert(&self, tag: Tag) {
+		if let Some(old_tag) = self.tags.get(&tag.name) {
+			self.unlink_aliases(&old_tag);
+		}
+		for alias in &tag.meta.aliases {
+			let mut targets = self
+				.aliases
+				.get(alias.as_str())
+				.map_or_else(Vec::new, |it| (*it).clone());
+			targets.push(tag.name.clone());
+			self.aliases.insert(alias.as_str().into(), targets);
+		}
+		self.tags.insert(tag.name.clone(), tag);
+	}
+
+	fn remove(&self, name: &str) -> Option<Arc<Tag>> {
+		let old_tag = self.tags.remove(name)?;
+		self.unlink_aliases(&old_tag);
+		Some(old_tag)
+	}
+
+	fn unlink_aliases(&self, tag: &Tag) {
+		for alias in &tag.meta.aliases {
+			let Some(targets) = self.aliases.get(alias.as_str()) else {
+				continue;
+			};
+			let mut targets = (*targets).clone();
+			targets.retain(|it| *it != tag.name);
+			if targets.is_empty() {
+				self.aliases.remove(alias.as_str());
+			} else {
+				self.aliases.insert(alias.as_str().into(), targets);
+			}
+		}
+	}
+
+	fn empty(path: PathBuf) -> TagHandler {
+		TagHandler {
+			tags: CowHashMap::new(),
+			aliases: CowHashMap::new(),
+			write_handle: Mutex::new(path),
+			load_errors: Mutex::new(vec![]),
+		}
+	}
+
+	async fn load() -> eyre::Result<TagHandler> {
+		let handler = TagHandler::empty(PathBuf::from("tags"));
+		handler.reload().await?;
+		Ok(handler)
+	}
+}
+
+/// Parse `<name> <revision>`, where the revision may be written as `3` or `r3`.
+fn parse_revision(text: &str) -> Option<(&str, usize)> {
+	let (key, revision) = text.trim().split_once(' ')?;
+	let revision = revision.trim();
+	let revision = revision
+		.strip_prefix('r')
+		.unwrap_or(revision)
+		.parse()
+		.ok()?;
+	Some((key, revision))
+}
+
+/// Point out that a command went to the tag an alias belongs to, rather than to a tag of that name.
+fn alias_note(given: &str, name: &str) -> String {
+	match given == name {
+		true => String::new(),
+		false => format!(", which `{given}` is an alias of"),
+	}
+}
+
+/// Reply with an explanation if a user provided tag name is invalid. Returns whether the name is valid.
+async fn check_tag_name(
+	context: &EventWithContext<&MessageCreate>,
+	name: &str,
+) -> eyre::Result<bool> {
+	match validate_tag_name(name) {
+		Ok(()) => Ok(true),
+		Err(err) => {
+			let text = format!("invalid tag name: {err}");
+			context
+				.reply()
+				.content(&text)
+				.allowed_mentions(Some(&AllowedMentions::default()))
+				.await?;
+			Ok(false)
+		}
+	}
+}
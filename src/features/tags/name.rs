@@ -0,0 +1,115 @@
+use std::fmt::Display;
+
+pub const MAX_TAG_NAME_LENGTH: usize = 32;
+const TAG_FILE_EXTENSION: &str = ".md";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InvalidTagName {
+	Empty,
+	TooLong,
+	InvalidCharacter(char),
+	/// Names have to start with a letter or digit, so they can never be `.`, `..` or a hidden file.
+	InvalidStart,
+	ConsecutiveDots,
+}
+
+impl Display for InvalidTagName {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			InvalidTagName::Empty => write!(f, "tag names cannot be empty"),
+			InvalidTagName::TooLong => {
+				write!(
+					f,
+					"tag names can be at most {MAX_TAG_NAME_LENGTH} characters long"
+				)
+			}
+			InvalidTagName::InvalidCharacter(c) => write!(
+				f,
+				"tag names may only contain letters, digits, `-`, `_` and `.`, not `{}`",
+				c.escape_default()
+			),
+			InvalidTagName::InvalidStart => {
+				write!(f, "tag names have to start with a letter or digit")
+			}
+			InvalidTagName::ConsecutiveDots => write!(f, "tag names cannot contain `..`"),
+		}
+	}
+}
+
+impl std::error::Error for InvalidTagName {}
+
+/// Tag names are ASCII letters, digits, `-`, `_` and `.`, starting with a letter or digit. This keeps them
+/// typeable after a `!`, and makes them safe to use as file names as is.
+pub fn validate_tag_name(name: &str) -> Result<(), InvalidTagName> {
+	let Some(first) = name.chars().next() else {
+		return Err(InvalidTagName::Empty);
+	};
+	if name.len() > MAX_TAG_NAME_LENGTH {
+		return Err(InvalidTagName::TooLong);
+	}
+	if let Some(c) = name
+		.chars()
+		.find(|&c| !c.is_ascii_alphanumeric() && !matches!(c, '-' | '_' | '.'))
+	{
+		return Err(InvalidTagName::InvalidCharacter(c));
+	}
+	if !first.is_ascii_alphanumeric() {
+		return Err(InvalidTagName::InvalidStart);
+	}
+	if name.contains("..") {
+		return Err(InvalidTagName::ConsecutiveDots);
+	}
+	Ok(())
+}
+
+/// The file a tag is stored in. Only call this with validated names.
+pub fn tag_file_name(name: &str) -> String {
+	debug_assert_eq!(validate_tag_name(name), Ok(()));
+	format!("{name}{TAG_FILE_EXTENSION}")
+}
+
+/// The reverse of [`tag_file_name`].
+pub fn tag_name_from_file(file_name: &str) -> eyre::Result<&str> {
+	let name = file_name
+		.strip_suffix(TAG_FILE_EXTENSION)
+		.ok_or_else(|| eyre::eyre!("tag files have to end in {TAG_FILE_EXTENSION}"))?;
+	validate_tag_name(name)?;
+	Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::features::tags::name::{InvalidTagName, tag_name_from_file, validate_tag_name};
+
+	#[test]
+	fn test_tag_names() {
+		assert_eq!(validate_tag_name("install"), Ok(()));
+		assert_eq!(validate_tag_name("v1.2"), Ok(()));
+		assert_eq!(validate_tag_name("Fabric-API_docs"), Ok(()));
+		assert_eq!(validate_tag_name(""), Err(InvalidTagName::Empty));
+		assert_eq!(
+			validate_tag_name(&"a".repeat(33)),
+			Err(InvalidTagName::TooLong)
+		);
+		assert_eq!(
+			validate_tag_name("../etc"),
+			Err(InvalidTagName::InvalidCharacter('/'))
+		);
+		assert_eq!(
+			validate_tag_name(".hidden"),
+			Err(InvalidTagName::InvalidStart)
+		);
+		assert_eq!(
+			validate_tag_name("a..b"),
+			Err(InvalidTagName::ConsecutiveDots)
+		);
+		assert_eq!(
+			validate_tag_name("ｆａｑ"),
+			Err(InvalidTagName::InvalidCharacter('ｆ'))
+		);
+
+		assert_eq!(tag_name_from_file("v1.2.md").unwrap(), "v1.2");
+		assert!(tag_name_from_file("faq.txt").is_err());
+		assert!(tag_name_from_file("a b.md").is_err());
+	}
+}
@@ -0,0 +1,198 @@
+use std::{collections::HashSet, fmt::Display, path::Path, sync::Arc, time::Duration};
+
+use eyre::Context as _;
+use notify::{RecursiveMode, Watcher as _};
+
+use super::{
+	TagHandler,
+	name::{tag_name_from_file, validate_tag_name},
+	tag::Tag,
+};
+
+/// Editors and git write files in several steps, so wait for things to settle down before reloading.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// What a reload changed, by tag name.
+#[derive(Debug, Default)]
+pub struct ReloadSummary {
+	pub added: Vec<Arc<str>>,
+	pub changed: Vec<Arc<str>>,
+	pub removed: Vec<Arc<str>>,
+	pub errors: usize,
+}
+
+impl ReloadSummary {
+	pub const fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+	}
+}
+
+impl Display for ReloadSummary {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let parts = [
+			("added", &self.added),
+			("changed", &self.changed),
+			("removed", &self.removed),
+		]
+		.into_iter()
+		.filter(|(_, names)| !names.is_empty())
+		.map(|(label, names)| {
+			let names = names
+				.iter()
+				.map(|it| format!("`{it}`"))
+				.intersperse(", ".to_owned())
+				.collect::<String>();
+			format!("{label} {names}")
+		})
+		.intersperse("; ".to_owned())
+		.collect::<String>();
+		if parts.is_empty() {
+			write!(f, "no changes")?;
+		} else {
+			write!(f, "{parts}")?;
+		}
+		if self.errors > 0 {
+			write!(f, " ({} tag files failed to load)", self.errors)?;
+		}
+		Ok(())
+	}
+}
+
+impl TagHandler {
+	/// Reread the tags folder and apply any differences to the loaded tags.
+	pub async fn reload(&self) -> eyre::Result<ReloadSummary> {
+		// Hold the write handle, so that we never read a tag file while it is being written
+		let path = self.write_handle.lock().await;
+		let mut summary = ReloadSummary::default();
+		let mut errors = vec![];
+		let mut seen = HashSet::new();
+		match tokio::fs::read_dir(&*path).await {
+			Ok(mut dir) => {
+				while let Some(file) = dir.next_entry().await? {
+					let file_name = file.file_name().to_string_lossy().into_owned();
+					let tag = match read_tag_file(&file.path(), &file_name).await {
+						Ok(tag) => tag,
+						Err(err) => {
+							tracing::warn!(?err, "Failed to load tag file {file_name}");
+							errors.push(format!("`{file_name}`: {err}"));
+							continue;
+						}
+					};
+					seen.insert(tag.name.clone());
+					match self.tags.get(&tag.name) {
+						Some(old_tag) if old_tag.to_file_content()? == tag.to_file_content()? => {
+							continue;
+						}
+						Some(_) => summary.changed.push(tag.name.clone()),
+						None => summary.added.push(tag.name.clone()),
+					}
+					self.insert(tag);
+				}
+			}
+			Err(err) => tracing::warn!(?err, "Failed to read tags folder"),
+		}
+		for name in self.tags.keys() {
+			if !seen.contains(&name) {
+				self.remove(&name);
+				summary.removed.push(name);
+			}
+		}
+		summary.errors = errors.len();
+		*self.load_errors.lock().await = errors;
+		Ok(summary)
+	}
+}
+
+async fn read_tag_file(path: &Path, file_name: &str) -> eyre::Result<Tag> {
+	let name = tag_name_from_file(file_name)?;
+	let content = tokio::fs::read_to_string(path).await?;
+	let tag = Tag::parse(name, &content)?;
+	for alias in &tag.meta.aliases {
+		validate_tag_name(alias).wrap_err_with(|| format!("invalid alias `{alias}`"))?;
+	}
+	Ok(tag)
+}
+
+/// Reload the tags whenever something in the tags folder changes, for example after a git pull.
+pub fn watch(handler: Arc<TagHandler>) {
+	let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+	let watcher = notify::recommended_watcher(move |event| {
+		_ = sender.send(event);
+	});
+	let mut watcher = match watcher {
+		Ok(watcher) => watcher,
+		Err(err) => {
+			tracing::warn!(?err, "Failed to create tags folder watcher");
+			return;
+		}
+	};
+	tokio::spawn(async move {
+		let path = handler.write_handle.lock().await.clone();
+		let watched = match tokio::fs::create_dir_all(&path).await {
+			Ok(()) => watcher.watch(&path, RecursiveMode::NonRecursive),
+			Err(err) => Err(err.into()),
+		};
+		if let Err(err) = watched {
+			tracing::warn!(?err, "Failed to watch the tags folder");
+			return;
+		}
+		while let Some(event) = receiver.recv().await {
+			match event {
+				// Reloading reads every file, which must not trigger another reload
+				Ok(event) if event.kind.is_access() => continue,
+				Ok(_) => {}
+				Err(err) => {
+					tracing::warn!(?err, "Tags folder watcher failed");
+					continue;
+				}
+			}
+			tokio::time::sleep(DEBOUNCE).await;
+			while receiver.try_recv().is_ok() {}
+			match handler.reload().await {
+				Ok(summary) if summary.is_empty() => {}
+				Ok(summary) => tracing::info!("Reloaded tags: {summary}"),
+				Err(err) => tracing::warn!(?err, "Failed to reload tags"),
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::features::tags::TagHandler;
+
+	#[tokio::test]
+	async fn test_reload() {
+		let path = std::env::temp_dir().join(format!("helios-reload-{}", std::process::id()));
+		tokio::fs::create_dir_all(&path).await.unwrap();
+		tokio::fs::write(path.join("faq.md"), "Read the docs")
+			.await
+			.unwrap();
+		tokio::fs::write(path.join("broken"), "").await.unwrap();
+		let handler = TagHandler::empty(path.clone());
+		let summary = handler.reload().await.unwrap();
+		assert_eq!(summary.added.len(), 1);
+		assert_eq!(summary.errors, 1);
+		assert!(handler.reload().await.unwrap().is_empty());
+
+		tokio::fs::write(
+			path.join("faq.md"),
+			"+++\naliases = [\"docs\"]\n+++\nRead them",
+		)
+		.await
+		.unwrap();
+		tokio::fs::write(path.join("install.md"), "Download it")
+			.await
+			.unwrap();
+		let summary = handler.reload().await.unwrap();
+		assert_eq!(&*summary.changed, [From::from("faq")]);
+		assert_eq!(&*summary.added, [From::from("install")]);
+		assert_eq!(handler.get("docs").unwrap().content, "Read them");
+
+		tokio::fs::remove_file(path.join("faq.md")).await.unwrap();
+		let summary = handler.reload().await.unwrap();
+		assert_eq!(&*summary.removed, [From::from("faq")]);
+		assert!(handler.get("docs").is_none());
+		_ = tokio::fs::remove_dir_all(path).await;
+	}
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use twilight_model::{
+	channel::Message,
+	id::{
+		Id,
+		marker::{ChannelMarker, GuildMarker},
+	},
+};
+
+use crate::HeliosCache;
+
+/// Where a tag can be used. A tag without a scope works everywhere. Scoped tags may share a name or alias with
+/// other tags, in which case the most specific one is used.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagScope {
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub guilds: Vec<Id<GuildMarker>>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub categories: Vec<Id<ChannelMarker>>,
+	/// Threads count as part of their parent channel.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub channels: Vec<Id<ChannelMarker>>,
+}
+
+/// Where a tag is being invoked.
+#[derive(Clone, Copy, Debug)]
+pub struct TagLocation {
+	pub guild: Option<Id<GuildMarker>>,
+	pub category: Option<Id<ChannelMarker>>,
+	pub channel: Id<ChannelMarker>,
+}
+
+impl TagLocation {
+	pub fn of(cache: &HeliosCache, message: &Message) -> TagLocation {
+		let parent = |channel| cache.channel(channel).and_then(|it| it.parent_id);
+		let is_thread = cache
+			.channel(message.channel_id)
+			.is_some_and(|it| it.kind.is_thread());
+		let channel = match is_thread {
+			true => parent(message.channel_id).unwrap_or(message.channel_id),
+			false => message.channel_id,
+		};
+		TagLocation {
+			guild: message.guild_id,
+			category: parent(channel),
+			channel,
+		}
+	}
+}
+
+impl TagScope {
+	/// How specifically this scope targets a location, or `None` if it does not include the location at all.
+	pub fn specificity(&self, location: &TagLocation) -> Option<u8> {
+		if self.channels.contains(&location.channel) {
+			Some(3)
+		} else if location
+			.category
+			.is_some_and(|it| self.categories.contains(&it))
+		{
+			Some(2)
+		} else if location.guild.is_some_and(|it| self.guilds.contains(&it)) {
+			Some(1)
+		} else {
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use twilight_model::id::Id;
+
+	use crate::features::tags::{TagHandler, scope::TagLocation, tag::Tag};
+
+	#[test]
+	fn test_most_specific_tag() {
+		let handler = TagHandler::empty("tags".into());
+		handler.insert(Tag::parse("install", "Download it").unwrap());
+		handler.insert(
+			Tag::parse(
+				"install-fabric",
+				"+++\naliases = [\"install\"]\n[scope]\ncategories = [10]\n+++\nUse fabric",
+			)
+			.unwrap(),
+		);
+		handler.insert(
+			Tag::parse(
+				"install-quilt",
+				"+++\naliases = [\"install\"]\n[scope]\nchannels = [\"21\"]\n+++\nUse quilt",
+			)
+			.unwrap(),
+		);
+		handler.insert(
+			Tag::parse(
+				"ban",
+				"+++\n[scope]\nguilds = [1]\nchannels = [99]\n+++\nBanned",
+			)
+			.unwrap(),
+		);
+		let location = |channel, category: Option<u64>| TagLocation {
+			guild: Some(Id::new(1)),
+			category: category.map(Id::new),
+			channel: Id::new(channel),
+		};
+		let content = |name, location| {
+			handler
+				.resolve(name, &location)
+				.map(|it| it.content.clone())
+		};
+		assert_eq!(
+			content("install", location(20, None)).as_deref(),
+			Some("Download it")
+		);
+		assert_eq!(
+			content("install", location(20, Some(10))).as_deref(),
+			Some("Use fabric")
+		);
+		assert_eq!(
+			content("install", location(21, Some(10))).as_deref(),
+			Some("Use quilt")
+		);
+		assert_eq!(content("install-quilt", location(20, None)), None);
+		assert_eq!(
+			content("ban", location(20, None)).as_deref(),
+			Some("Banned")
+		);
+		let elsewhere = TagLocation {
+			guild: Some(Id::new(2)),
+			category: None,
+			channel: Id::new(20),
+		};
+		assert_eq!(content("ban", elsewhere), None);
+
+		handler.remove("install-quilt");
+		assert_eq!(
+			content("install", location(21, Some(10))).as_deref(),
+			Some("Use fabric")
+		);
+	}
+}
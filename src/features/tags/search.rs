@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use unicase::UniCase;
+
+use super::{TagHandler, scope::TagLocation, tag::Tag};
+use crate::utils::is_claimed_command;
+
+/// How many typos a name of this length may contain and still be suggested.
+const fn max_distance(length: usize) -> usize {
+	match length {
+		0..=3 => 1,
+		_ => 2,
+	}
+}
+
+fn fold(text: &str) -> String {
+	UniCase::new(text).to_folded_case()
+}
+
+impl TagHandler {
+	/// Every name a tag can be invoked by at a location, including aliases.
+	fn invocable_names(&self, location: &TagLocation) -> Vec<Arc<str>> {
+		let mut names = vec![];
+		for tag in self.tags.values() {
+			if tag.specificity(location).is_some() {
+				names.push(tag.name.clone());
+				names.extend(tag.meta.aliases.iter().map(|it| Arc::from(it.as_str())));
+			}
+		}
+		names
+	}
+
+	/// Find the closest tag name or alias usable at a location to a misspelled word, if any is close enough.
+	pub fn suggest(&self, word: &str, location: &TagLocation) -> Option<Arc<str>> {
+		// Commands of other features are never misspelled tag names
+		if is_claimed_command(word) {
+			return None;
+		}
+		let word = fold(word);
+		self.invocable_names(location)
+			.into_iter()
+			.map(|name| (strsim::damerau_levenshtein(&word, &fold(&name)), name))
+			.filter(|(distance, name)| *distance <= max_distance(name.len()))
+			.min_by(|(a_distance, a), (b_distance, b)| {
+				a_distance.cmp(b_distance).then_with(|| a.cmp(b))
+			})
+			.map(|(_, name)| name)
+	}
+
+	/// Search the tags usable at a location by name, alias, description and content. Best matches come first.
+	pub fn search(&self, query: &str, location: &TagLocation) -> Vec<Arc<Tag>> {
+		let query = fold(query.trim());
+		let mut results = self
+			.tags
+			.values()
+			.filter(|tag| tag.specificity(location).is_some())
+			.filter_map(|tag| {
+				let names = std::iter::once(&*tag.name)
+					.chain(tag.meta.aliases.iter().map(String::as_str))
+					.map(fold)
+					.collect::<Vec<_>>();
+				let score = if names.iter().any(|it| it.contains(&query)) {
+					0
+				} else if names
+					.iter()
+					.any(|it| strsim::damerau_levenshtein(&query, it) <= max_distance(it.len()))
+				{
+					1
+				} else if tag
+					.meta
+					.description
+					.as_deref()
+					.is_some_and(|it| fold(it).contains(&query))
+				{
+					2
+				} else if fold(&tag.content).contains(&query) {
+					3
+				} else {
+					return None;
+				};
+				Some((score, tag.clone()))
+			})
+			.collect::<Vec<_>>();
+		results.sort_by(|(a_score, a), (b_score, b)| {
+			a_score.cmp(b_score).then_with(|| a.name.cmp(&b.name))
+		});
+		results.into_iter().map(|(_, tag)| tag).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use twilight_model::id::Id;
+
+	use crate::features::tags::{TagHandler, scope::TagLocation, tag::Tag};
+
+	#[test]
+	fn test_suggestions() {
+		let handler = TagHandler::empty("tags".into());
+		handler
+			.insert(Tag::parse("install", "+++\naliases = [\"setup\"]\n+++\nDownload it").unwrap());
+		handler.insert(Tag::parse("faq", "Read the docs").unwrap());
+		handler.insert(
+			Tag::parse(
+				"secret",
+				"+++\naliases = [\"hidden\"]\n[scope]\nchannels = [2]\n+++\nStaff docs",
+			)
+			.unwrap(),
+		);
+		let location = |channel| TagLocation {
+			guild: Some(Id::new(1)),
+			category: None,
+			channel: Id::new(channel),
+		};
+		let suggest = |word| handler.suggest(word, &location(1));
+		assert_eq!(suggest("instal").as_deref(), Some("install"));
+		assert_eq!(suggest("Setpu").as_deref(), Some("setup"));
+		assert_eq!(suggest("fag").as_deref(), Some("faq"));
+		assert_eq!(suggest("time"), None);
+		assert_eq!(suggest("uninstallation"), None);
+		// Scoped tags are not given away outside of their scope
+		assert_eq!(suggest("secrte"), None);
+		assert_eq!(suggest("hiden"), None);
+		assert_eq!(
+			handler.suggest("hiden", &location(2)).as_deref(),
+			Some("hidden")
+		);
+
+		let names = |query| {
+			handler
+				.search(query, &location(1))
+				.iter()
+				.map(|it| it.name.to_string())
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(names("docs"), ["faq"]);
+		assert_eq!(names("set"), ["install"]);
+		assert!(names("nothing like this").is_empty());
+		assert!(names("staff").is_empty());
+	}
+}
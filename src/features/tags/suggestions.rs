@@ -0,0 +1,225 @@
+use std::{collections::HashMap, env};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use twilight_http::request::channel::reaction::RequestReactionType;
+use twilight_model::{
+	channel::message::{AllowedMentions, EmojiReactionType},
+	gateway::payload::incoming::{MessageCreate, ReactionAdd},
+	id::{
+		Id,
+		marker::{ChannelMarker, MessageMarker, UserMarker},
+	},
+};
+use twilight_util::builder::embed::EmbedBuilder;
+
+use super::{check_tag_name, tag_handler};
+use crate::{
+	EventWithContext, handle, handle_message,
+	utils::{persist, user_perms},
+};
+
+handle_message!(should_reply, on_suggest);
+handle!(ReactionAdd, on_review_reaction);
+
+const SUGGESTIONS_PATH: &str = "tag_suggestions.json";
+const APPROVE: &str = "✅";
+const REJECT: &str = "❌";
+
+/// A tag suggestion waiting for review.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Suggestion {
+	name: String,
+	content: String,
+	suggester: Id<UserMarker>,
+	channel_id: Id<ChannelMarker>,
+	message_id: Id<MessageMarker>,
+	/// The existing tag the suggestion changes, if any. It may be suggested under one of its aliases.
+	#[serde(default)]
+	target: Option<String>,
+}
+
+/// Pending suggestions, keyed by their message in the review channel.
+static SUGGESTIONS: Mutex<Option<HashMap<Id<MessageMarker>, Suggestion>>> = Mutex::const_new(None);
+
+/// Channel where suggestions are posted for staff to review. Configured using the `TAG_REVIEW_CHANNEL` env var.
+fn review_channel() -> Option<Id<ChannelMarker>> {
+	env::var("TAG_REVIEW_CHANNEL").ok()?.parse().ok()
+}
+
+async fn on_suggest(context: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
+	let Some(rest) = context.content.strip_prefix("!tag suggest ") else {
+		return Ok(());
+	};
+	let Some((name, content)) = rest.split_once(' ') else {
+		context
+			.reply()
+			.content("use: !tag suggest <name> <content>")
+			.await?;
+		return Ok(());
+	};
+	if !check_tag_name(&context, name).await? {
+		return Ok(());
+	}
+	let Some(review_channel) = review_channel() else {
+		tracing::warn!("Got a tag suggestion, but TAG_REVIEW_CHANNEL is not set");
+		context
+			.reply()
+			.content("tag suggestions are not set up right now, sorry.")
+			.await?;
+		return Ok(());
+	};
+
+	let handler = tag_handler().await;
+	let target = handler.get(name).map(|it| it.name.to_string());
+	let kind = if target.is_some() {
+		"Change to the existing tag"
+	} else {
+		"New tag"
+	};
+	let embed = EmbedBuilder::new()
+		.title(format!("{kind} `{name}`"))
+		.description(content)
+		.build();
+	let text = format!(
+		"<@{}> suggested a tag. React with {APPROVE} to save it or {REJECT} to reject it.",
+		context.author.id
+	);
+	let review_message = context
+		.client
+		.create_message(review_channel)
+		.content(&text)
+		.embeds(&[embed])
+		.allowed_mentions(Some(&AllowedMentions::default()))
+		.await?
+		.model()
+		.await?;
+	for emoji in [APPROVE, REJECT] {
+		context
+			.client
+			.create_reaction(
+				review_channel,
+				review_message.id,
+				&RequestReactionType::Unicode { name: emoji },
+			)
+			.await?;
+	}
+
+	let suggestion = Suggestion {
+		name: name.to_owned(),
+		content: content.to_owned(),
+		suggester: context.author.id,
+		channel_id: context.channel_id,
+		message_id: context.id,
+		target,
+	};
+	let mut suggestions = SUGGESTIONS.lock().await;
+	let suggestions = match &mut *suggestions {
+		Some(suggestions) => suggestions,
+		None => suggestions.insert(persist::load_json(SUGGESTIONS_PATH).await?),
+	};
+	suggestions.insert(review_message.id, suggestion);
+	persist::save_json(SUGGESTIONS_PATH, suggestions).await?;
+
+	context
+		.reply()
+		.content("thanks! your suggestion has been sent to staff for review.")
+		.await?;
+	Ok(())
+}
+
+async fn on_review_reaction(event: EventWithContext<&ReactionAdd>) -> eyre::Result<()> {
+	if Some(event.channel_id) != review_channel() {
+		return Ok(());
+	}
+	let EmojiReactionType::Unicode { name: emoji } = &event.emoji else {
+		return Ok(());
+	};
+	let approved = match emoji.as_str() {
+		APPROVE => true,
+		REJECT => false,
+		_ => return Ok(()),
+	};
+	let Some(member) = &event.member else {
+		return Ok(());
+	};
+	if !user_perms(&member.user, Some(&member.roles)).should_obey() {
+		return Ok(());
+	}
+
+	let mut suggestions = SUGGESTIONS.lock().await;
+	let suggestions = match &mut *suggestions {
+		Some(suggestions) => suggestions,
+		None => suggestions.insert(persist::load_json(SUGGESTIONS_PATH).await?),
+	};
+	let Some(suggestion) = suggestions.get(&event.message_id).cloned() else {
+		return Ok(());
+	};
+
+	let outcome = if approved {
+		let handler = tag_handler().await;
+		// Tags may have been added or renamed while the suggestion was waiting for review
+		let target = handler.get(&suggestion.name).map(|it| it.name.to_string());
+		let conflict = match handler.conflicting_name(&suggestion.name) {
+			Some(existing) => Some(format!("conflicts with the existing tag `{existing}`")),
+			None if target != suggestion.target => match target {
+				Some(target) => Some(format!("is now used by the tag `{target}`")),
+				None => Some("no longer exists".to_owned()),
+			},
+			None => None,
+		};
+		if let Some(conflict) = conflict {
+			let text = format!(
+				"<@{}> suggested a tag, but `{}` {conflict}. React with {REJECT} to reject it.",
+				suggestion.suggester, suggestion.name
+			);
+			event
+				.client
+				.update_message(event.channel_id, event.message_id)
+				.content(Some(&text))
+				.await?;
+			return Ok(());
+		}
+		handler
+			.write_tag(
+				&suggestion.name,
+				Some(&suggestion.content),
+				suggestion.suggester,
+			)
+			.await?;
+		"approved"
+	} else {
+		"rejected"
+	};
+	// Only forget the suggestion once it is dealt with, so a failed write can be approved again
+	suggestions.remove(&event.message_id);
+	persist::save_json(SUGGESTIONS_PATH, suggestions).await?;
+	tracing::info!(
+		"Tag suggestion {} by {} was {outcome} by {}",
+		suggestion.name,
+		suggestion.suggester,
+		event.user_id
+	);
+
+	let text = format!(
+		"<@{}> suggested a tag. It was {outcome} by <@{}>.",
+		suggestion.suggester, event.user_id
+	);
+	event
+		.client
+		.update_message(event.channel_id, event.message_id)
+		.content(Some(&text))
+		.await?;
+	let text = format!(
+		"your suggestion for the tag `{}` was {outcome}.",
+		suggestion.name
+	);
+	event
+		.client
+		.create_message(suggestion.channel_id)
+		.reply(suggestion.message_id)
+		.fail_if_not_exists(false)
+		.content(&text)
+		.await?;
+	Ok(())
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use twilight_model::{
+	channel::message::Embed,
+	id::{Id, marker::UserMarker},
+};
+use twilight_util::builder::embed::{EmbedBuilder, ImageSource};
+
+use super::scope::{TagLocation, TagScope};
+
+const FRONT_MATTER_FENCE: &str = "+++";
+
+#[derive(Clone, Debug)]
+pub struct Tag {
+	pub name: Arc<str>,
+	pub meta: TagMeta,
+	pub content: String,
+}
+
+/// Optional TOML front matter of a tag file, fenced by `+++` lines.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagMeta {
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub aliases: Vec<String>,
+	/// Shown in `!tag list`.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub author: Option<Id<UserMarker>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub last_editor: Option<Id<UserMarker>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub created: Option<DateTime<Utc>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub updated: Option<DateTime<Utc>>,
+	/// If present, the tag is sent as an embed with the content as its description.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub embed: Option<TagEmbed>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub scope: Option<TagScope>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagEmbed {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub title: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub color: Option<u32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub image: Option<String>,
+}
+
+impl Tag {
+	/// Parse the contents of a tag file, splitting off the front matter if there is any.
+	pub fn parse(name: &str, file_content: &str) -> eyre::Result<Tag> {
+		let (meta, content) = match file_content
+			.strip_prefix(FRONT_MATTER_FENCE)
+			.and_then(|it| it.strip_prefix('\n'))
+			.and_then(|it| it.split_once(&format!("\n{FRONT_MATTER_FENCE}")))
+		{
+			Some((front_matter, content)) => (
+				toml::from_str(front_matter)?,
+				content.strip_prefix('\n').unwrap_or(content),
+			),
+			None => (TagMeta::default(), file_content),
+		};
+		Ok(Tag {
+			name: name.into(),
+			meta,
+			content: content.to_owned(),
+		})
+	}
+
+	/// Serialize this tag into the contents of a tag file. Tags without any metadata are written as plain text.
+	pub fn to_file_content(&self) -> eyre::Result<String> {
+		if self.meta == TagMeta::default() {
+			return Ok(self.content.clone());
+		}
+		Ok(format!(
+			"{FRONT_MATTER_FENCE}\n{}{FRONT_MATTER_FENCE}\n{}",
+			toml::to_string(&self.meta)?,
+			self.content
+		))
+	}
+
+	/// How specifically this tag targets a location, or `None` if it cannot be used there. Unscoped tags are the least
+	/// specific.
+	pub fn specificity(&self, location: &TagLocation) -> Option<u8> {
+		match &self.meta.scope {
+			Some(scope) => scope.specificity(location),
+			None => Some(0),
+		}
+	}
+
+	/// Build the embed for this tag, if it is in embed mode. Takes the rendered content as the description.
+	pub fn embed(&self, content: &str) -> eyre::Result<Option<Embed>> {
+		let Some(embed) = &self.meta.embed else {
+			return Ok(None);
+		};
+		let mut builder = EmbedBuilder::new()
+			.title(embed.title.as_deref().unwrap_or(&self.name))
+			.description(content);
+		if let Some(color) = embed.color {
+			builder = builder.color(color);
+		}
+		if let Some(image) = &embed.image {
+			builder = builder.image(ImageSource::url(image)?);
+		}
+		Ok(Some(builder.build()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::Utc;
+	use twilight_model::id::Id;
+
+	use crate::features::tags::tag::Tag;
+
+	#[test]
+	fn test_front_matter() {
+		let plain = Tag::parse("plain", "just some text\n+++\n").unwrap();
+		assert_eq!(plain.content, "just some text\n+++\n");
+		assert_eq!(plain.to_file_content().unwrap(), plain.content);
+
+		let file = "+++\naliases = [\"setup\"]\ndescription = \"How to install\"\n\n[embed]\ncolor = 255\n+++\nDownload the jar.";
+		let mut tag = Tag::parse("install", file).unwrap();
+		assert_eq!(tag.meta.aliases, ["setup"]);
+		assert_eq!(tag.meta.description.as_deref(), Some("How to install"));
+		assert_eq!(tag.meta.embed.as_ref().unwrap().color, Some(255));
+		assert_eq!(tag.content, "Download the jar.");
+		tag.meta.author = Some(Id::new(310702108997320705));
+		tag.meta.created = Some(Utc::now());
+		let reparsed = Tag::parse("install", &tag.to_file_content().unwrap()).unwrap();
+		assert_eq!(reparsed.meta, tag.meta);
+		assert_eq!(reparsed.content, tag.content);
+
+		assert!(Tag::parse("broken", "+++\nunknown = 1\n+++\n").is_err());
+	}
+}
@@ -0,0 +1,291 @@
+use std::{collections::HashMap, pin::Pin, time::Duration};
+
+use cached::proc_macro::cached;
+use twilight_model::id::{
+	Id,
+	marker::{ChannelMarker, UserMarker},
+};
+
+use crate::utils::consts::FIRMAMENT_REPO;
+
+/// Everything a tag template can refer to.
+pub struct TemplateContext<'a> {
+	/// The person who invoked the tag.
+	pub author: Id<UserMarker>,
+	/// The person being helped: the first mentioned user, or the author of the message being replied to.
+	pub user: Id<UserMarker>,
+	pub channel: Id<ChannelMarker>,
+	/// Everything after the tag name, on the same line.
+	pub args: &'a str,
+}
+
+type HelperFuture = Pin<Box<dyn Future<Output = eyre::Result<String>> + Send>>;
+
+/// A value that can be used as `{name}` in a tag, computed on demand.
+pub struct TemplateHelper {
+	pub name: &'static str,
+	pub resolve: fn() -> HelperFuture,
+}
+
+inventory::collect!(TemplateHelper);
+
+inventory::submit! {
+	TemplateHelper {
+		name: "latest_release",
+		resolve: || Box::pin(latest_release()),
+	}
+}
+
+#[cached(time = 600, result = true)]
+async fn latest_release() -> eyre::Result<String> {
+	let release = octocrab::instance()
+		.repos_by_id(FIRMAMENT_REPO)
+		.releases()
+		.get_latest()
+		.await?;
+	Ok(release.tag_name)
+}
+
+#[derive(Debug, PartialEq)]
+enum Node<'a> {
+	Text(&'a str),
+	Variable(&'a str),
+	If {
+		condition: &'a str,
+		then: Vec<Node<'a>>,
+		otherwise: Vec<Node<'a>>,
+	},
+}
+
+enum Token<'a> {
+	If(&'a str),
+	Else,
+	End,
+	Variable(&'a str),
+}
+
+/// An `{if}` whose `{end}` has not been reached yet.
+struct OpenIf<'a> {
+	condition: &'a str,
+	/// The nodes before the `{if}`.
+	outer: Vec<Node<'a>>,
+	/// The nodes of the then branch, once an `{else}` has been reached.
+	then: Option<Vec<Node<'a>>>,
+}
+
+impl<'a> OpenIf<'a> {
+	fn close(self, body: Vec<Node<'a>>) -> Vec<Node<'a>> {
+		let (then, otherwise) = match self.then {
+			Some(then) => (then, body),
+			None => (body, vec![]),
+		};
+		let mut nodes = self.outer;
+		nodes.push(Node::If {
+			condition: self.condition,
+			then,
+			otherwise,
+		});
+		nodes
+	}
+}
+
+fn is_identifier(text: &str) -> bool {
+	!text.is_empty()
+		&& text
+			.chars()
+			.all(|it| it.is_ascii_alphanumeric() || it == '_')
+}
+
+/// Split a template into nodes. Braces that do not form a valid placeholder are kept as plain text, so that code
+/// snippets in tags keep working.
+fn parse(mut source: &str) -> Vec<Node<'_>> {
+	let mut stack: Vec<OpenIf> = vec![];
+	let mut nodes = vec![];
+	while !source.is_empty() {
+		let Some(start) = source.find('{') else {
+			nodes.push(Node::Text(source));
+			break;
+		};
+		let Some(length) = source[start..].find('}') else {
+			nodes.push(Node::Text(source));
+			break;
+		};
+		let inner = &source[start + 1..start + length];
+		let token =
+			if let Some(condition) = inner.strip_prefix("if ").filter(|it| is_identifier(it)) {
+				Some(Token::If(condition))
+			} else if inner == "else" && stack.last().is_some_and(|it| it.then.is_none()) {
+				Some(Token::Else)
+			} else if inner == "end" && !stack.is_empty() {
+				Some(Token::End)
+			} else if is_identifier(inner) {
+				Some(Token::Variable(inner))
+			} else {
+				None
+			};
+		let Some(token) = token else {
+			// Not a placeholder, keep the brace as text and continue after it
+			nodes.push(Node::Text(&source[..start + 1]));
+			source = &source[start + 1..];
+			continue;
+		};
+		if start > 0 {
+			nodes.push(Node::Text(&source[..start]));
+		}
+		source = &source[start + length + 1..];
+		match token {
+			Token::If(condition) => stack.push(OpenIf {
+				condition,
+				outer: std::mem::take(&mut nodes),
+				then: None,
+			}),
+			Token::Else => stack.last_mut().unwrap().then = Some(std::mem::take(&mut nodes)),
+			Token::End => nodes = stack.pop().unwrap().close(nodes),
+			Token::Variable(name) => nodes.push(Node::Variable(name)),
+		}
+	}
+	// Close any unterminated conditionals at the end of the template
+	while let Some(open) = stack.pop() {
+		nodes = open.close(nodes);
+	}
+	nodes
+}
+
+fn collect_variables<'a>(nodes: &[Node<'a>], into: &mut Vec<&'a str>) {
+	for node in nodes {
+		match node {
+			Node::Text(_) => {}
+			Node::Variable(name) => into.push(name),
+			Node::If {
+				condition,
+				then,
+				otherwise,
+			} => {
+				into.push(condition);
+				collect_variables(then, into);
+				collect_variables(otherwise, into);
+			}
+		}
+	}
+}
+
+fn render_nodes(nodes: &[Node], variables: &HashMap<&str, String>, into: &mut String) {
+	for node in nodes {
+		match node {
+			Node::Text(text) => into.push_str(text),
+			Node::Variable(name) => match variables.get(name) {
+				Some(value) => into.push_str(value),
+				None => {
+					into.push('{');
+					into.push_str(name);
+					into.push('}');
+				}
+			},
+			Node::If {
+				condition,
+				then,
+				otherwise,
+			} => {
+				let is_set = variables.get(condition).is_some_and(|it| !it.is_empty());
+				render_nodes(if is_set { then } else { otherwise }, variables, into);
+			}
+		}
+	}
+}
+
+/// Neutralize anything that could ping people, so user provided text can be pasted into a reply.
+pub fn escape_mentions(text: &str) -> String {
+	text.replace('@', "@\u{200B}")
+}
+
+fn variables(context: &TemplateContext) -> HashMap<&'static str, String> {
+	let mut variables = HashMap::new();
+	variables.insert("user", format!("<@{}>", context.user));
+	variables.insert("author", format!("<@{}>", context.author));
+	variables.insert("channel", format!("<#{}>", context.channel));
+	variables.insert("args", escape_mentions(context.args));
+	const ARG_NAMES: [&str; 9] = [
+		"arg1", "arg2", "arg3", "arg4", "arg5", "arg6", "arg7", "arg8", "arg9",
+	];
+	for (name, arg) in ARG_NAMES.iter().zip(context.args.split_whitespace()) {
+		variables.insert(name, escape_mentions(arg));
+	}
+	variables
+}
+
+/// Evaluate the placeholders in a tag. Helper output is escaped, so only `{user}` and `{author}` can mention anyone.
+pub async fn render(template: &str, context: &TemplateContext<'_>) -> String {
+	let nodes = parse(template);
+	let mut names = vec![];
+	collect_variables(&nodes, &mut names);
+	let mut variables = variables(context);
+	for helper in inventory::iter::<TemplateHelper> {
+		if !names.contains(&helper.name) || variables.contains_key(helper.name) {
+			continue;
+		}
+		let value = match (helper.resolve)().await {
+			Ok(value) => escape_mentions(&value),
+			Err(err) => {
+				tracing::warn!(?err, "Failed to resolve template helper {}", helper.name);
+				"???".to_owned()
+			}
+		};
+		variables.insert(helper.name, value);
+	}
+	let mut output = String::new();
+	render_nodes(&nodes, &variables, &mut output);
+	output
+}
+
+#[cfg(test)]
+mod tests {
+	use twilight_model::id::Id;
+
+	use crate::features::tags::template::{TemplateContext, render};
+
+	async fn render_with_args(template: &str, args: &str) -> String {
+		let context = TemplateContext {
+			author: Id::new(1),
+			user: Id::new(2),
+			channel: Id::new(3),
+			args,
+		};
+		render(template, &context).await
+	}
+
+	#[tokio::test]
+	async fn test_placeholders() {
+		assert_eq!(
+			render_with_args("hi {user}, {author} sent you to {channel}", "").await,
+			"hi <@2>, <@1> sent you to <#3>"
+		);
+		assert_eq!(
+			render_with_args("{arg2} {arg1}! ({args})", "a b").await,
+			"b a! (a b)"
+		);
+		assert_eq!(
+			render_with_args("fun main() { println({unknown}) }", "").await,
+			"fun main() { println({unknown}) }"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_conditionals() {
+		let template = "{if arg1}version {arg1}{if arg2} on {arg2}{end}{else}which version?{end}.";
+		assert_eq!(render_with_args(template, "").await, "which version?.");
+		assert_eq!(render_with_args(template, "1.2").await, "version 1.2.");
+		assert_eq!(
+			render_with_args(template, "1.2 fabric").await,
+			"version 1.2 on fabric."
+		);
+		assert_eq!(render_with_args("{end}{else}", "").await, "{end}{else}");
+	}
+
+	#[tokio::test]
+	async fn test_arguments_cannot_mention() {
+		assert_eq!(
+			render_with_args("{args}", "@everyone <@&123>").await,
+			"@\u{200B}everyone <@\u{200B}&123>"
+		);
+	}
+}
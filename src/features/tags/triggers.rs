@@ -0,0 +1,108 @@
+/// A `!name` in a message, along with the rest of its line.
+#[derive(Debug, PartialEq)]
+pub struct Trigger<'a> {
+	pub name: &'a str,
+	pub args: &'a str,
+}
+
+/// Where a code block, code span or link that starts at the beginning of `text` ends, if it does.
+fn skip_ignored(text: &str) -> Option<usize> {
+	if let Some(rest) = text.strip_prefix("```") {
+		return rest.find("```").map(|end| end + 6);
+	}
+	if let Some(rest) = text.strip_prefix('`') {
+		return rest.find('`').map(|end| end + 2);
+	}
+	if text.starts_with("http://") || text.starts_with("https://") {
+		return Some(text.find(char::is_whitespace).unwrap_or(text.len()));
+	}
+	None
+}
+
+/// Find all tag invocations in a message, in order. Anything in code or links is ignored, so that pasted logs
+/// and URLs do not trigger tags.
+pub fn find_triggers(content: &str) -> Vec<Trigger<'_>> {
+	let mut triggers = vec![];
+	let mut index = 0;
+	while let Some(rest) = content.get(index..).filter(|it| !it.is_empty()) {
+		if let Some(length) = skip_ignored(rest) {
+			index += length;
+			continue;
+		}
+		if let Some(rest) = rest.strip_prefix('!') {
+			let name = &rest[..rest.find(char::is_whitespace).unwrap_or(rest.len())];
+			let line = &rest[name.len()..];
+			let args = &line[..line.find('\n').unwrap_or(line.len())];
+			if !name.is_empty() {
+				triggers.push(Trigger {
+					name,
+					args: args.trim(),
+				});
+			}
+		}
+		index += rest.chars().next().map_or(1, char::len_utf8);
+	}
+	triggers
+}
+
+/// Split a text into pages of at most `limit` bytes, preferably between lines.
+pub fn paginate(text: &str, limit: usize) -> Vec<String> {
+	let mut pages = vec![];
+	let mut page = String::new();
+	for mut line in text.split_inclusive('\n') {
+		if page.len() + line.len() > limit && !page.is_empty() {
+			pages.push(std::mem::take(&mut page));
+		}
+		while line.len() > limit {
+			let mut split = limit;
+			while !line.is_char_boundary(split) {
+				split -= 1;
+			}
+			pages.push(line[..split].to_owned());
+			line = &line[split..];
+		}
+		page += line;
+	}
+	if !page.trim().is_empty() {
+		pages.push(page);
+	}
+	pages
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::features::tags::triggers::{Trigger, find_triggers, paginate};
+
+	#[test]
+	fn test_triggers() {
+		let names = |content| {
+			find_triggers(content)
+				.into_iter()
+				.map(|it| it.name)
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(names("!a !b !a\n!c"), ["a", "b", "a", "c"]);
+		assert_eq!(names("see `!a` and\n```\n!b\n```\n!c"), ["c"]);
+		assert_eq!(
+			names("https://example.com/!a?x=!b <https://x.y/!c> !d"),
+			["d"]
+		);
+		assert_eq!(names("unclosed ``` `!a and !b"), ["a", "b"]);
+		assert_eq!(names("wow! nice!"), Vec::<&str>::new());
+		assert_eq!(
+			find_triggers("!version 1.2 fabric\nthanks")[0],
+			Trigger {
+				name: "version",
+				args: "1.2 fabric"
+			}
+		);
+	}
+
+	#[test]
+	fn test_paginate() {
+		assert_eq!(paginate("a\nb\nc", 4), ["a\nb\n", "c"]);
+		assert_eq!(paginate("abcdef", 4), ["abcd", "ef"]);
+		assert_eq!(paginate("ééé", 4), ["éé", "é"]);
+		assert!(paginate("", 4).is_empty());
+	}
+}
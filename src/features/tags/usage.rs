@@ -0,0 +1,180 @@
+use std::{collections::HashMap, fmt::Display, sync::Arc};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{io::AsyncWriteExt as _, sync::Mutex};
+use twilight_model::id::{
+	Id,
+	marker::{ChannelMarker, UserMarker},
+};
+
+pub const USAGE_PATH: &str = "tag_usage.csv";
+
+/// Serializes appends, so that concurrent invocations never interleave their lines.
+static USAGE_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// One invocation of a tag, as a line in the usage log.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Usage {
+	pub timestamp: DateTime<Utc>,
+	/// The name of the tag that was sent, even if it was invoked through an alias.
+	pub tag: String,
+	pub channel: Id<ChannelMarker>,
+	pub user: Id<UserMarker>,
+}
+
+pub async fn record(usage: &Usage) -> eyre::Result<()> {
+	let _lock = USAGE_LOCK.lock().await;
+	let has_header = tokio::fs::try_exists(USAGE_PATH).await?;
+	let mut writer = csv::WriterBuilder::new()
+		.has_headers(!has_header)
+		.from_writer(vec![]);
+	writer.serialize(usage)?;
+	let line = writer.into_inner()?;
+	let mut file = tokio::fs::OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(USAGE_PATH)
+		.await?;
+	file.write_all(&line).await?;
+	Ok(())
+}
+
+/// Read the whole usage log. Unreadable lines are skipped.
+pub async fn load() -> eyre::Result<Vec<Usage>> {
+	let _lock = USAGE_LOCK.lock().await;
+	let content = match tokio::fs::read(USAGE_PATH).await {
+		Ok(content) => content,
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+		Err(err) => return Err(err.into()),
+	};
+	let usages = csv::Reader::from_reader(&content[..])
+		.into_deserialize()
+		.filter_map(|it| {
+			it.inspect_err(|err| tracing::warn!(?err, "Skipping broken tag usage line"))
+				.ok()
+		})
+		.collect();
+	Ok(usages)
+}
+
+/// Tag usage over the last weeks.
+#[derive(Debug)]
+pub struct UsageStats {
+	/// Uses in the last 30 days, and the change from the week before the last to the last week. Most used first.
+	pub top: Vec<(Arc<str>, usize, isize)>,
+	/// Tags without a single use in the last 30 days.
+	pub unused: Vec<Arc<str>>,
+	pub total: usize,
+}
+
+impl UsageStats {
+	pub fn compute(
+		usages: &[Usage],
+		tags: impl IntoIterator<Item = Arc<str>>,
+		now: DateTime<Utc>,
+	) -> UsageStats {
+		let month = now - TimeDelta::days(30);
+		let week = now - TimeDelta::days(7);
+		let fortnight = now - TimeDelta::days(14);
+		let mut counts = tags
+			.into_iter()
+			.map(|tag| (tag, (0usize, 0isize)))
+			.collect::<HashMap<_, _>>();
+		let mut total = 0;
+		for usage in usages.iter().filter(|it| it.timestamp > month) {
+			// Deleted tags are not interesting anymore
+			let Some((uses, trend)) = counts.get_mut(usage.tag.as_str()) else {
+				continue;
+			};
+			total += 1;
+			*uses += 1;
+			if usage.timestamp > week {
+				*trend += 1;
+			} else if usage.timestamp > fortnight {
+				*trend -= 1;
+			}
+		}
+		let mut unused = vec![];
+		let mut top = vec![];
+		for (tag, (uses, trend)) in counts {
+			if uses == 0 {
+				unused.push(tag);
+			} else {
+				top.push((tag, uses, trend));
+			}
+		}
+		unused.sort();
+		top.sort_by(|(a, a_uses, _), (b, b_uses, _)| b_uses.cmp(a_uses).then_with(|| a.cmp(b)));
+		UsageStats { top, unused, total }
+	}
+}
+
+impl Display for UsageStats {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		const MAX_TOP: usize = 10;
+		const MAX_UNUSED: usize = 30;
+		writeln!(f, "**{} tag uses in the last 30 days**", self.total)?;
+		for (tag, uses, trend) in self.top.iter().take(MAX_TOP) {
+			let arrow = match trend.signum() {
+				1 => "↗",
+				-1 => "↘",
+				_ => "→",
+			};
+			writeln!(f, "`{tag}`: {uses} {arrow} ({trend:+} this week)")?;
+		}
+		if !self.unused.is_empty() {
+			let names = self
+				.unused
+				.iter()
+				.take(MAX_UNUSED)
+				.map(|it| format!("`{it}`"))
+				.intersperse(", ".to_owned())
+				.collect::<String>();
+			write!(f, "**unused:** {names}")?;
+			if self.unused.len() > MAX_UNUSED {
+				write!(f, " and {} more", self.unused.len() - MAX_UNUSED)?;
+			}
+			writeln!(f)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{TimeDelta, Utc};
+	use twilight_model::id::Id;
+
+	use crate::features::tags::usage::{Usage, UsageStats};
+
+	#[test]
+	fn test_usage_stats() {
+		let now = Utc::now();
+		let usage = |tag: &str, days_ago| Usage {
+			timestamp: now - TimeDelta::days(days_ago),
+			tag: tag.to_owned(),
+			channel: Id::new(1),
+			user: Id::new(2),
+		};
+		let usages = [
+			usage("faq", 1),
+			usage("faq", 2),
+			usage("install", 10),
+			usage("install", 11),
+			usage("install", 12),
+			usage("old", 40),
+			usage("deleted", 1),
+		];
+		let tags = ["faq", "install", "old", "never"].map(From::from);
+		let stats = UsageStats::compute(&usages, tags, now);
+		assert_eq!(stats.total, 5);
+		let top = stats
+			.top
+			.iter()
+			.map(|(tag, uses, trend)| (&**tag, *uses, *trend))
+			.collect::<Vec<_>>();
+		assert_eq!(top, [("install", 3, -3), ("faq", 2, 2)]);
+		assert_eq!(stats.unused, ["never", "old"].map(From::from));
+	}
+}
@@ -0,0 +1,192 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Weekday};
+
+/// Which day a time expression refers to, relative to today in the place it is about.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateSpec {
+	Today,
+	/// `tomorrow` and `yesterday`.
+	Offset(i64),
+	/// The next day with this weekday, or today if it is that day already.
+	Weekday(Weekday),
+	Date(NaiveDate),
+}
+
+/// Something like `tomorrow 9am` or `18:30 2025-06-01`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeExpression {
+	pub date: DateSpec,
+	pub time: NaiveTime,
+}
+
+impl DateSpec {
+	pub fn resolve(self, today: NaiveDate) -> NaiveDate {
+		match self {
+			DateSpec::Today => today,
+			DateSpec::Offset(days) => today + TimeDelta::days(days),
+			DateSpec::Weekday(weekday) => {
+				let days = (weekday.num_days_from_monday() + 7
+					- today.weekday().num_days_from_monday())
+					% 7;
+				today + TimeDelta::days(days.into())
+			}
+			DateSpec::Date(date) => date,
+		}
+	}
+}
+
+impl TimeExpression {
+	pub fn resolve(&self, today: NaiveDate) -> NaiveDateTime {
+		self.date.resolve(today).and_time(self.time)
+	}
+}
+
+/// Parse `today`, `tomorrow`, a weekday, `2025-06-01`, `01.06.2025` or `01.06.`. Expects the word in lowercase.
+pub fn parse_date(word: &str, today: NaiveDate) -> Option<DateSpec> {
+	match word {
+		"today" => return Some(DateSpec::Today),
+		"tomorrow" => return Some(DateSpec::Offset(1)),
+		"yesterday" => return Some(DateSpec::Offset(-1)),
+		_ => {}
+	}
+	const WEEKDAYS: [&str; 7] = [
+		"monday",
+		"tuesday",
+		"wednesday",
+		"thursday",
+		"friday",
+		"saturday",
+		"sunday",
+	];
+	if let Some(index) = WEEKDAYS.iter().position(|it| *it == word) {
+		return Weekday::try_from(index as u8).ok().map(DateSpec::Weekday);
+	}
+	if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+		return Some(DateSpec::Date(date));
+	}
+	if let Ok(date) = NaiveDate::parse_from_str(word, "%d.%m.%Y") {
+		return Some(DateSpec::Date(date));
+	}
+	// 24.12. means the next 24th of December
+	let (day, month) = word.strip_suffix('.')?.split_once('.')?;
+	let date = NaiveDate::from_ymd_opt(today.year(), month.parse().ok()?, day.parse().ok()?)?;
+	match date < today {
+		true => date.with_year(today.year() + 1).map(DateSpec::Date),
+		false => Some(DateSpec::Date(date)),
+	}
+}
+
+/// Parse `18:30`, `9am`, `9:15pm`, `noon` or `midnight`. A bare hour needs an `am` or `pm`, which may also be the
+/// next word.
+fn parse_time(word: &str, next: Option<&str>) -> Option<(NaiveTime, bool)> {
+	match word {
+		"noon" => return Some((NaiveTime::from_hms_opt(12, 0, 0)?, false)),
+		"midnight" => return Some((NaiveTime::MIN, false)),
+		_ => {}
+	}
+	let (clock, meridiem, used_next) = if let Some(clock) = word.strip_suffix("am") {
+		(clock, Some(false), false)
+	} else if let Some(clock) = word.strip_suffix("pm") {
+		(clock, Some(true), false)
+	} else {
+		match next {
+			Some("am") => (word, Some(false), true),
+			Some("pm") => (word, Some(true), true),
+			_ => (word, None, false),
+		}
+	};
+	let (hour, minute) = match clock.split_once(':') {
+		Some((hour, minute)) if minute.len() == 2 => (hour, minute.parse().ok()?),
+		Some(_) => return None,
+		None if meridiem.is_some() => (clock, 0),
+		None => return None,
+	};
+	let mut hour: u32 = hour.parse().ok()?;
+	if let Some(pm) = meridiem {
+		if !(1..=12).contains(&hour) {
+			return None;
+		}
+		hour = hour % 12 + if pm { 12 } else { 0 };
+	}
+	Some((NaiveTime::from_hms_opt(hour, minute, 0)?, used_next))
+}
+
+/// Split a time expression off the start of a `!time` query, returning the expression and the rest of the query.
+/// The date may come before or after the time, but a time is required.
+pub fn parse_expression(text: &str, today: NaiveDate) -> Option<(TimeExpression, String)> {
+	let words = text.split_whitespace().collect::<Vec<_>>();
+	let mut date = None;
+	let mut time = None;
+	let mut index = 0;
+	while index < words.len() {
+		let word = words[index].to_lowercase();
+		if date.is_none() {
+			if let Some(spec) = parse_date(&word, today) {
+				date = Some(spec);
+				index += 1;
+				continue;
+			}
+		}
+		if time.is_none() {
+			let next = words.get(index + 1).map(|it| it.to_lowercase());
+			if let Some((parsed, used_next)) = parse_time(&word, next.as_deref()) {
+				time = Some(parsed);
+				index += 1 + usize::from(used_next);
+				continue;
+			}
+		}
+		break;
+	}
+	let expression = TimeExpression {
+		date: date.unwrap_or(DateSpec::Today),
+		time: time?,
+	};
+	Some((expression, words[index..].join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{NaiveDate, NaiveTime, Weekday};
+
+	use crate::features::time::expr::{DateSpec, TimeExpression, parse_expression};
+
+	#[test]
+	fn test_time_expressions() {
+		// A Sunday
+		let today = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+		let parse = |text| parse_expression(text, today);
+		let at = |date, hour, minute| TimeExpression {
+			date,
+			time: NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+		};
+
+		assert_eq!(
+			parse("18:30 berlin to tokyo"),
+			Some((at(DateSpec::Today, 18, 30), "berlin to tokyo".to_owned()))
+		);
+		assert_eq!(
+			parse("tomorrow 9am new york"),
+			Some((at(DateSpec::Offset(1), 9, 0), "new york".to_owned()))
+		);
+		assert_eq!(
+			parse("12 AM Friday"),
+			Some((at(DateSpec::Weekday(Weekday::Fri), 0, 0), String::new()))
+		);
+		assert_eq!(parse("9:15pm").unwrap().0, at(DateSpec::Today, 21, 15));
+		let christmas = NaiveDate::from_ymd_opt(2026, 12, 24).unwrap();
+		assert_eq!(
+			parse("24.12. noon").unwrap().0,
+			at(DateSpec::Date(christmas), 12, 0)
+		);
+		assert_eq!(parse("berlin"), None);
+		assert_eq!(parse("tomorrow berlin"), None);
+		assert_eq!(parse("25:00 berlin"), None);
+		assert_eq!(parse("13pm"), None);
+
+		let friday = NaiveDate::from_ymd_opt(2026, 10, 23).unwrap();
+		assert_eq!(
+			parse("friday 8:00").unwrap().0.resolve(today).date(),
+			friday
+		);
+		assert_eq!(parse("sunday 8:00").unwrap().0.resolve(today).date(), today);
+	}
+}
@@ -0,0 +1,441 @@
+use std::{
+	collections::HashMap,
+	env,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::UNIX_EPOCH,
+};
+
+use chrono_tz::{TZ_VARIANTS, Tz};
+use csv::StringRecord;
+use eyre::{Context, OptionExt, bail};
+use positioned_io::RandomAccessFile;
+use rc_zip_tokio::ReadZip;
+use tokio::sync::OnceCell;
+use unicase::UniCase;
+
+use super::{GeoEntry, index::GeoIndex};
+use crate::utils::{cached, persist};
+
+/// Where the geonames dump comes from unless `GEONAMES_SOURCE` says otherwise.
+const DEFAULT_SOURCE: &str = "https://download.geonames.org/export/dump";
+const CITIES_FILE: &str = "cities15000.zip";
+const COUNTRIES_FILE: &str = "countryInfo.txt";
+const ADMIN1_FILE: &str = "admin1CodesASCII.txt";
+
+/// The parsed dump, so restarts do not have to unzip and parse it again.
+const CACHE_PATH: &str = "geonames.bin";
+/// Bump the version whenever the layout of the cache changes.
+const CACHE_MAGIC: &[u8] = b"helios-geonames-1";
+/// The fewest bytes an entry takes up in the cache: its id, seven lengths, the population and the coordinates.
+const MIN_CACHED_ENTRY_SIZE: usize = 8 + 7 * 4 + 3 * 4;
+
+/// A URL or a local directory with the geonames `cities15000.zip`, `countryInfo.txt` and `admin1CodesASCII.txt`. The
+/// docker image bundles a copy of them and points `GEONAMES_SOURCE` at it.
+fn source() -> String {
+	env::var("GEONAMES_SOURCE").unwrap_or_else(|_| DEFAULT_SOURCE.to_owned())
+}
+
+fn is_url(source: &str) -> bool {
+	source.starts_with("http://") || source.starts_with("https://")
+}
+
+async fn fetch(source: &str, file: &str) -> eyre::Result<PathBuf> {
+	if is_url(source) {
+		let url = format!("{}/{file}", source.trim_end_matches('/'));
+		cached::download_url(url.clone())
+			.await
+			.wrap_err_with(|| format!("downloading {url}"))
+	} else {
+		Ok(Path::new(source).join(file))
+	}
+}
+
+/// Country names by ISO code, like `DE`, and region names by country and region code, like `US.TX`.
+#[derive(Debug, Default)]
+struct RegionNames {
+	countries: HashMap<String, String>,
+	admin1: HashMap<String, String>,
+}
+
+impl RegionNames {
+	async fn load(source: &str) -> eyre::Result<RegionNames> {
+		let mut names = RegionNames::default();
+		let countries = fetch(source, COUNTRIES_FILE).await?;
+		for line in tokio::fs::read_to_string(countries).await?.lines() {
+			let fields = line.split('\t').collect::<Vec<_>>();
+			if !line.starts_with('#') && fields.len() > 4 {
+				names
+					.countries
+					.insert(fields[0].to_owned(), fields[4].to_owned());
+			}
+		}
+		let admin1 = fetch(source, ADMIN1_FILE).await?;
+		for line in tokio::fs::read_to_string(admin1).await?.lines() {
+			if let Some((code, rest)) = line.split_once('\t') {
+				let name = rest.split('\t').next().unwrap_or_default();
+				names.admin1.insert(code.to_owned(), name.to_owned());
+			}
+		}
+		Ok(names)
+	}
+}
+
+fn fold_names(name: &str, alternatenames: &[String]) -> Vec<String> {
+	[name]
+		.into_iter()
+		.chain(alternatenames.iter().map(String::as_str))
+		.map(|it| UniCase::new(it).to_folded_case())
+		.collect()
+}
+
+// csv_reader.set_headers(StringRecord::from(vec![
+// 0	"geonameid",
+// 1	"name",
+// 2	"asciiname",
+// 3	"alternatenames",
+// 4	"latitude",
+// 5	"longitude",
+// 6	"feature class",
+// 7	"feature code",
+// 8	"country code",
+// 9	"cc2",
+// 10	"admin1 code",
+// 11	"admin2 code",
+// 12	"admin3 code",
+// 13	"admin4 code",
+// 14	"population",
+// 15	"elevation",
+// 16	"dem",
+// 17	"timezone",
+// 18	"modification date",
+// ]));
+fn parse_record(record: &StringRecord, regions: &RegionNames) -> eyre::Result<GeoEntry> {
+	let name = record[1].to_owned();
+	let alternatenames: Vec<String> = record[3].split(",").map(ToOwned::to_owned).collect();
+	let match_names = fold_names(&name, &alternatenames);
+	let country_code = record[8].to_owned();
+	let admin1_code = format!("{country_code}.{}", &record[10]);
+	let country = regions
+		.countries
+		.get(&country_code)
+		.cloned()
+		.unwrap_or_default();
+	let admin1 = regions
+		.admin1
+		.get(&admin1_code)
+		.cloned()
+		.unwrap_or_default();
+	let qualifier_names = [&country_code, &record[10], &country, &admin1]
+		.into_iter()
+		.filter(|it| !it.is_empty())
+		.map(|it| UniCase::new(it).to_folded_case())
+		.collect();
+	Ok(GeoEntry {
+		geonameid: record[0].parse().context("parsing geonameid")?,
+		name,
+		alternatenames,
+		latitude: record[4].parse().context("parsing latitude")?,
+		longitude: record[5].parse().context("parsing longitude")?,
+		timezone: record[17].parse().context("parsing timezone")?,
+		population: record[14].parse().context("parsing population")?,
+		match_names,
+		country_code,
+		country,
+		admin1,
+		qualifier_names,
+	})
+}
+
+/// Read the places from the geonames dump itself, and whether their country and region names could be loaded too.
+async fn load_dump(source: &str) -> eyre::Result<(Vec<GeoEntry>, bool)> {
+	let zip_path = fetch(source, CITIES_FILE).await?;
+	let zip_file = Arc::new(
+		RandomAccessFile::open(&zip_path)
+			.wrap_err_with(|| format!("opening {}", zip_path.display()))?,
+	);
+	let zip_archive = ReadZip::read_zip(&zip_file).await?;
+	// Each zip contains exactly one file
+	let cities_entry = zip_archive
+		.entries()
+		.next()
+		.ok_or_eyre("the cities zip is empty")?;
+	let cities_bytes = cities_entry.bytes().await?;
+	let csv_reader = csv::ReaderBuilder::default()
+		.has_headers(false)
+		.delimiter(b'\t')
+		.from_reader(&cities_bytes[..]);
+
+	let (regions, has_regions) = match RegionNames::load(source).await {
+		Ok(regions) => (regions, true),
+		Err(err) => {
+			tracing::error!(?err, "Failed to load country and region names");
+			(RegionNames::default(), false)
+		}
+	};
+
+	let mut v = Vec::new();
+	for record in csv_reader.into_records() {
+		let record = record?;
+		match parse_record(&record, &regions) {
+			Ok(entry) => v.push(entry),
+			Err(err) => {
+				tracing::error!(?err, "Failed to load city entry from {}", record.as_slice())
+			}
+		}
+	}
+	Ok((v, has_regions))
+}
+
+fn write_len(bytes: &mut Vec<u8>, len: usize) {
+	bytes.extend((len as u32).to_le_bytes());
+}
+
+fn write_str(bytes: &mut Vec<u8>, text: &str) {
+	write_len(bytes, text.len());
+	bytes.extend(text.as_bytes());
+}
+
+fn write_strs(bytes: &mut Vec<u8>, texts: &[String]) {
+	write_len(bytes, texts.len());
+	for text in texts {
+		write_str(bytes, text);
+	}
+}
+
+/// What the cache was made from: the source, and for local sources the size and modification time of every file, so
+/// the cache is made again when they are replaced.
+async fn cache_key(source: &str) -> String {
+	let mut key = source.to_owned();
+	if is_url(source) {
+		return key;
+	}
+	for file in [CITIES_FILE, COUNTRIES_FILE, ADMIN1_FILE] {
+		key += &match tokio::fs::metadata(Path::new(source).join(file)).await {
+			Ok(metadata) => {
+				let modified = metadata
+					.modified()
+					.ok()
+					.and_then(|it| it.duration_since(UNIX_EPOCH).ok())
+					.map_or(0, |it| it.as_secs());
+				format!("\n{file} {} {modified}", metadata.len())
+			}
+			Err(_) => format!("\n{file} missing"),
+		};
+	}
+	key
+}
+
+/// Encode places for the cache, remembering what they were made from. Folded names of places are left out, they are
+/// quick to recreate.
+fn encode_cache(key: &str, entries: &[GeoEntry]) -> Vec<u8> {
+	let mut bytes = CACHE_MAGIC.to_vec();
+	write_str(&mut bytes, key);
+	write_len(&mut bytes, entries.len());
+	for entry in entries {
+		bytes.extend((entry.geonameid as u64).to_le_bytes());
+		write_str(&mut bytes, &entry.name);
+		write_strs(&mut bytes, &entry.alternatenames);
+		write_str(&mut bytes, entry.timezone.name());
+		write_str(&mut bytes, &entry.country_code);
+		write_str(&mut bytes, &entry.country);
+		write_str(&mut bytes, &entry.admin1);
+		write_strs(&mut bytes, &entry.qualifier_names);
+		bytes.extend(entry.population.to_le_bytes());
+		bytes.extend(entry.latitude.to_le_bytes());
+		bytes.extend(entry.longitude.to_le_bytes());
+	}
+	bytes
+}
+
+/// Reads the cache front to back.
+struct CacheReader<'a>(&'a [u8]);
+
+impl<'a> CacheReader<'a> {
+	fn take<const N: usize>(&mut self) -> eyre::Result<[u8; N]> {
+		let Some((bytes, rest)) = self.0.split_first_chunk() else {
+			bail!("the cache ends early");
+		};
+		self.0 = rest;
+		Ok(*bytes)
+	}
+
+	fn len(&mut self) -> eyre::Result<usize> {
+		Ok(u32::from_le_bytes(self.take()?) as usize)
+	}
+
+	fn str(&mut self) -> eyre::Result<&'a str> {
+		let len = self.len()?;
+		if self.0.len() < len {
+			bail!("the cache ends early");
+		}
+		let (text, rest) = self.0.split_at(len);
+		self.0 = rest;
+		Ok(str::from_utf8(text)?)
+	}
+
+	fn string(&mut self) -> eyre::Result<String> {
+		self.str().map(ToOwned::to_owned)
+	}
+
+	fn strings(&mut self) -> eyre::Result<Vec<String>> {
+		(0..self.len()?).map(|_| self.string()).collect()
+	}
+}
+
+/// Decode the cache, or `None` if it was made from a different source or different files.
+fn decode_cache(key: &str, bytes: &[u8]) -> eyre::Result<Option<Vec<GeoEntry>>> {
+	let Some(bytes) = bytes.strip_prefix(CACHE_MAGIC) else {
+		return Ok(None);
+	};
+	let mut reader = CacheReader(bytes);
+	if reader.str()? != key {
+		return Ok(None);
+	}
+	let count = reader.len()?;
+	// The count is only trusted as far as the cache could actually hold that many entries
+	let mut entries = Vec::with_capacity(count.min(reader.0.len() / MIN_CACHED_ENTRY_SIZE));
+	for _ in 0..count {
+		let geonameid = u64::from_le_bytes(reader.take()?) as usize;
+		let name = reader.string()?;
+		let alternatenames = reader.strings()?;
+		let timezone: Tz = reader.str()?.parse()?;
+		entries.push(GeoEntry {
+			geonameid,
+			match_names: fold_names(&name, &alternatenames),
+			name,
+			alternatenames,
+			timezone,
+			country_code: reader.string()?,
+			country: reader.string()?,
+			admin1: reader.string()?,
+			qualifier_names: reader.strings()?,
+			population: u32::from_le_bytes(reader.take()?),
+			latitude: f32::from_le_bytes(reader.take()?),
+			longitude: f32::from_le_bytes(reader.take()?),
+		});
+	}
+	Ok(Some(entries))
+}
+
+/// Load the places from the cache, or from the dump if the cache is missing or stale.
+async fn load_places() -> eyre::Result<Vec<GeoEntry>> {
+	let source = source();
+	let key = cache_key(&source).await;
+	match tokio::fs::read(CACHE_PATH).await {
+		Ok(bytes) => match decode_cache(&key, &bytes) {
+			Ok(Some(entries)) => return Ok(entries),
+			Ok(None) => {}
+			Err(err) => tracing::warn!(?err, "Ignoring broken {CACHE_PATH}"),
+		},
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+		Err(err) => tracing::warn!(?err, "Could not read {CACHE_PATH}"),
+	}
+	let (entries, has_regions) = load_dump(&source).await?;
+	// Places without their countries and regions can not be told apart, so they are only good until the next start
+	if !has_regions {
+		return Ok(entries);
+	}
+	if let Err(err) = persist::save_bytes(CACHE_PATH, encode_cache(&key, &entries)).await {
+		tracing::warn!(?err, "Could not write {CACHE_PATH}");
+	}
+	Ok(entries)
+}
+
+/// All known places and timezones. If the places cannot be loaded, only timezones can be found by their names.
+pub async fn geo_database() -> &'static GeoIndex {
+	static GEO_ENTRY: OnceCell<GeoIndex> = tokio::sync::OnceCell::const_new();
+	GEO_ENTRY
+		.get_or_init(|| async {
+			let mut v = load_places().await.unwrap_or_else(|err| {
+				tracing::error!(
+					?err,
+					"Failed to load places, only timezone names can be looked up"
+				);
+				vec![]
+			});
+			for tz in TZ_VARIANTS {
+				v.push(GeoEntry {
+					geonameid: usize::MAX,
+					name: tz.name().split('/').next_back().unwrap().to_owned(),
+					alternatenames: vec![],
+					timezone: tz,
+					match_names: vec![UniCase::new(tz.name()).to_folded_case()],
+					country_code: String::new(),
+					country: String::new(),
+					admin1: String::new(),
+					qualifier_names: vec![],
+					population: u32::MAX,
+					latitude: f32::NAN,
+					longitude: f32::NAN,
+				});
+			}
+			GeoIndex::new(v)
+		})
+		.await
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono_tz::Asia;
+
+	use crate::features::time::{
+		GeoEntry,
+		geonames::{COUNTRIES_FILE, cache_key, decode_cache, encode_cache, fold_names},
+	};
+
+	#[test]
+	fn test_cache_round_trip() {
+		let alternatenames = vec!["東京".to_owned(), "Tokio".to_owned()];
+		let entry = GeoEntry {
+			geonameid: 1850147,
+			match_names: fold_names("Tokyo", &alternatenames),
+			name: "Tokyo".to_owned(),
+			alternatenames,
+			timezone: Asia::Tokyo,
+			country_code: "JP".to_owned(),
+			country: "Japan".to_owned(),
+			admin1: "Tokyo".to_owned(),
+			qualifier_names: vec!["jp".to_owned(), "japan".to_owned()],
+			population: 8336599,
+			latitude: 35.6895,
+			longitude: 139.69171,
+		};
+		let bytes = encode_cache("here", &[entry.clone()]);
+		let decoded = decode_cache("here", &bytes).unwrap().unwrap();
+		assert_eq!(format!("{decoded:?}"), format!("{:?}", [entry]));
+
+		assert!(decode_cache("elsewhere", &bytes).unwrap().is_none());
+		assert!(decode_cache("here", &bytes[..bytes.len() - 1]).is_err());
+		assert!(decode_cache("here", b"something else").unwrap().is_none());
+
+		// A broken count must not make it reserve room for billions of entries
+		let mut bytes = encode_cache("here", &[]);
+		let count = bytes.len() - 4;
+		bytes[count..].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(decode_cache("here", &bytes).is_err());
+	}
+
+	#[tokio::test]
+	async fn test_cache_key_follows_local_files() {
+		let source = std::env::temp_dir().join(format!("helios-geonames-{}", std::process::id()));
+		tokio::fs::create_dir_all(&source).await.unwrap();
+		let source_name = source.to_str().unwrap();
+		let missing = cache_key(source_name).await;
+		tokio::fs::write(source.join(COUNTRIES_FILE), "DE")
+			.await
+			.unwrap();
+		let written = cache_key(source_name).await;
+		tokio::fs::write(source.join(COUNTRIES_FILE), "DE\nFR")
+			.await
+			.unwrap();
+		let changed = cache_key(source_name).await;
+		assert_ne!(missing, written);
+		assert_ne!(written, changed);
+		assert_eq!(
+			cache_key("https://example.com").await,
+			"https://example.com"
+		);
+		_ = tokio::fs::remove_dir_all(source).await;
+	}
+}
@@ -0,0 +1,194 @@
+use super::{GeoEntry, PlaceQuery};
+
+/// How many times more entries a word may have than the candidates so far to still be worth intersecting with.
+const INTERSECT_RATIO: usize = 16;
+
+/// A prefix index over the folded words of every place's names, regions and countries, so searching does not have to
+/// look at every place.
+pub struct GeoIndex {
+	entries: Vec<GeoEntry>,
+	/// Every word with the index of the entry it belongs to, sorted by word.
+	words: Vec<(Box<str>, u32)>,
+}
+
+impl GeoIndex {
+	pub fn new(entries: Vec<GeoEntry>) -> GeoIndex {
+		let mut words = vec![];
+		for (id, entry) in entries.iter().enumerate() {
+			for name in entry.match_names.iter().chain(&entry.qualifier_names) {
+				for word in name.split_whitespace() {
+					words.push((word.into(), id as u32));
+				}
+			}
+		}
+		words.sort_unstable();
+		words.dedup();
+		GeoIndex { entries, words }
+	}
+
+	/// The place with this geonames id.
+	pub fn get(&self, geonameid: usize) -> Option<&GeoEntry> {
+		self.entries.iter().find(|it| it.geonameid == geonameid)
+	}
+
+	/// All words starting with the prefix. They are next to each other since the words are sorted.
+	fn prefixed(&self, prefix: &str) -> &[(Box<str>, u32)] {
+		let start = self.words.partition_point(|(word, _)| &**word < prefix);
+		let len = self.words[start..].partition_point(|(word, _)| word.starts_with(prefix));
+		&self.words[start..start + len]
+	}
+
+	/// All entries matching the query. Every word of the query has to start some word of a matching entry, so the
+	/// candidates are the intersection of each word's entries, starting with the rarest word. Words common enough to
+	/// hardly narrow anything down, like a country, are left to checking the candidates against the query.
+	pub fn search(&self, query: &PlaceQuery) -> Vec<&GeoEntry> {
+		let mut words = query
+			.words()
+			.map(|word| self.prefixed(word))
+			.collect::<Vec<_>>();
+		words.sort_by_key(|it| it.len());
+		let Some((rarest, rest)) = words.split_first() else {
+			return vec![];
+		};
+		let ids = |words: &[(Box<str>, u32)]| {
+			let mut ids = words.iter().map(|(_, id)| *id).collect::<Vec<_>>();
+			ids.sort_unstable();
+			ids.dedup();
+			ids
+		};
+		let mut candidates = ids(rarest);
+		for words in rest {
+			if words.len() > candidates.len() * INTERSECT_RATIO {
+				break;
+			}
+			let ids = ids(words);
+			candidates.retain(|id| ids.binary_search(id).is_ok());
+		}
+		candidates
+			.into_iter()
+			.map(|id| &self.entries[id as usize])
+			.filter(|it| query.matches(it))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate test;
+
+	use chrono_tz::TZ_VARIANTS;
+	use test::Bencher;
+	use unicase::UniCase;
+
+	use crate::features::time::{GeoEntry, PlaceQuery, index::GeoIndex};
+
+	const SYLLABLES: [&str; 16] = [
+		"ka", "lo", "mi", "ber", "san", "to", "ri", "un", "vel", "dor", "a", "sta", "ne", "port",
+		"ham", "is",
+	];
+
+	/// About as many made up places as there are cities in the geonames dump, with a few alternate names each.
+	fn places() -> Vec<GeoEntry> {
+		let mut seed = 0x2545_f491_u32;
+		let mut random = move |bound: usize| {
+			seed ^= seed << 13;
+			seed ^= seed >> 17;
+			seed ^= seed << 5;
+			seed as usize % bound
+		};
+		let mut word = || {
+			(0..2 + random(3))
+				.map(|_| SYLLABLES[random(SYLLABLES.len())])
+				.collect::<String>()
+		};
+		(0..30_000)
+			.map(|id| {
+				let name = word();
+				let alternatenames = (0..5).map(|_| word()).collect::<Vec<_>>();
+				let match_names = [&name]
+					.into_iter()
+					.chain(&alternatenames)
+					.map(|it| UniCase::new(it).to_folded_case())
+					.collect();
+				let admin1 = word();
+				GeoEntry {
+					geonameid: id,
+					name,
+					alternatenames,
+					timezone: TZ_VARIANTS[id % TZ_VARIANTS.len()],
+					match_names,
+					country_code: String::new(),
+					country: "Testland".to_owned(),
+					qualifier_names: vec![admin1.clone(), "testland".to_owned()],
+					admin1,
+					population: id as u32,
+					latitude: 0.0,
+					longitude: 0.0,
+				}
+			})
+			.collect()
+	}
+
+	/// Searches the way people write them: a whole name, the start of one, a name with its region, a qualified name
+	/// and a typo.
+	fn queries(places: &[GeoEntry]) -> Vec<String> {
+		vec![
+			places[100].name.to_lowercase(),
+			places[2000].name[..4].to_lowercase(),
+			format!("{} {}", places[15000].name, places[15000].admin1),
+			format!("{}, testland", places[29000].alternatenames[0]),
+			"velxdor".to_owned(),
+		]
+	}
+
+	#[test]
+	fn test_index_matches_scan() {
+		let index = GeoIndex::new(places());
+		assert_eq!(index.get(2000).unwrap().geonameid, 2000);
+		assert!(index.get(30_000).is_none());
+		let queries = queries(&index.entries);
+		let queries = queries
+			.iter()
+			.map(String::as_str)
+			.chain(["", "testland", "a", "lo mi"]);
+		for query in queries {
+			let query = PlaceQuery::new(query);
+			let scanned = index
+				.entries
+				.iter()
+				.filter(|it| query.matches(it))
+				.map(|it| it.geonameid)
+				.collect::<Vec<_>>();
+			let indexed = index
+				.search(&query)
+				.into_iter()
+				.map(|it| it.geonameid)
+				.collect::<Vec<_>>();
+			assert_eq!(indexed, scanned);
+		}
+	}
+
+	#[bench]
+	fn bench_scan(bencher: &mut Bencher) {
+		let entries = places();
+		let queries = queries(&entries);
+		bencher.iter(|| {
+			for query in &queries {
+				let query = PlaceQuery::new(query);
+				test::black_box(entries.iter().filter(|it| query.matches(it)).count());
+			}
+		});
+	}
+
+	#[bench]
+	fn bench_index(bencher: &mut Bencher) {
+		let index = GeoIndex::new(places());
+		let queries = queries(&index.entries);
+		bencher.iter(|| {
+			for query in &queries {
+				let query = PlaceQuery::new(query);
+				test::black_box(index.search(&query).len());
+			}
+		});
+	}
+}
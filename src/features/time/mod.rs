@@ -0,0 +1,533 @@
+use core::f32;
+use std::sync::Arc;
+
+use chrono::{
+	DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc,
+	offset::LocalResult,
+};
+use chrono_tz::Tz;
+use twilight_model::{
+	gateway::payload::incoming::MessageCreate,
+	id::{Id, marker::UserMarker},
+};
+use unicase::UniCase;
+
+use self::{
+	expr::TimeExpression,
+	geonames::geo_database,
+	offset::parse_offset_zone,
+	zones::{SavedZone, zone_book},
+};
+use crate::{EventWithContext, claim_command, handle_message, utils::args};
+
+mod expr;
+mod geonames;
+mod index;
+mod offset;
+mod sun;
+mod timestamp;
+mod zones;
+
+handle_message!(should_reply, on_post_time);
+claim_command!("time");
+
+async fn on_post_time(event: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
+	if event.content == "!time" {
+		let author = event.author.id;
+		if !post_saved_time(&event, author).await? {
+			post_time(event, "frankfurt").await?;
+		}
+	} else if event.content.trim() == "!time set" {
+		event.reply().content("use: !time set <place>").await?;
+	} else if let Some(place) = event.content.strip_prefix("!time set ") {
+		save_time(event, place).await?;
+	} else if let Some(rest) = event.content.strip_prefix("!time ") {
+		match args::chomp_user(rest) {
+			Some((user, _)) => {
+				if !post_saved_time(&event, user).await? {
+					let text = format!(
+						"<@{user}> has not saved their timezone yet. They can do so using `!time set <place>`."
+					);
+					event.reply().content(&text).await?;
+				}
+			}
+			None => match expr::parse_expression(rest, Utc::now().date_naive()) {
+				Some((expression, places)) => convert_time(event, expression, &places).await?,
+				None => post_time(event, rest).await?,
+			},
+		}
+	}
+	Ok(())
+}
+
+async fn save_time(event: EventWithContext<&MessageCreate>, place: &str) -> eyre::Result<()> {
+	// A fixed offset would be wrong for half of the year wherever clocks change
+	if parse_offset_zone(place).is_some() {
+		let text = format!(
+			"{place} is a fixed offset, which does not follow daylight saving time. Save the place you are in or a timezone like Europe/Berlin instead."
+		);
+		event.reply().content(&text).await?;
+		return Ok(());
+	}
+	let places = find_places(place).await;
+	let Some(entry) = places.first() else {
+		let text = format!("Could not find a place called {place}.");
+		event.reply().content(&text).await?;
+		return Ok(());
+	};
+	let zone = SavedZone {
+		place: entry.name.clone(),
+		timezone: entry.timezone.name().to_owned(),
+		geonameid: entry.is_place().then_some(entry.geonameid),
+	};
+	let mut zones = zone_book().await?;
+	zones.users.insert(event.author.id, zone);
+	zones.save().await?;
+	let mut text = format!(
+		"Saved your timezone as **{}** ({}).",
+		entry.display_name(),
+		entry.timezone.name()
+	);
+	if let Some(note) = ambiguity_note(&places, "!time set") {
+		text += "\n";
+		text += &note;
+	}
+	event.reply().content(&text).await?;
+	Ok(())
+}
+
+/// A timezone by name, or a fixed offset from one, like `UTC+2`.
+#[derive(Clone, Copy, Debug)]
+enum Zone {
+	Named(Tz),
+	Fixed(FixedOffset),
+}
+
+impl Zone {
+	fn localize(self, local: &NaiveDateTime) -> LocalResult<DateTime<FixedOffset>> {
+		match self {
+			Zone::Named(tz) => tz.from_local_datetime(local).map(|it| it.fixed_offset()),
+			Zone::Fixed(offset) => offset.from_local_datetime(local),
+		}
+	}
+
+	fn convert(self, time: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+		match self {
+			Zone::Named(tz) => time.with_timezone(&tz).fixed_offset(),
+			Zone::Fixed(offset) => time.with_timezone(&offset),
+		}
+	}
+
+	fn today(self) -> NaiveDate {
+		self.convert(Utc::now().fixed_offset()).date_naive()
+	}
+}
+
+/// Where a time is meant, as resolved from a user provided place.
+struct Place {
+	name: String,
+	/// The timezone name or offset.
+	label: String,
+	zone: Zone,
+}
+
+/// Find the timezone of a place, replying with an error if there is none. An empty place means the author's own
+/// saved timezone, or Frankfurt if they have none.
+async fn resolve_place(
+	event: &EventWithContext<&MessageCreate>,
+	place: &str,
+) -> eyre::Result<Option<Place>> {
+	if place.is_empty() {
+		let zone = zone_book().await?.users.get(&event.author.id).cloned();
+		let (name, timezone) = zone
+			.and_then(|it| Some((it.place.clone(), it.timezone()?)))
+			.unwrap_or_else(|| ("Frankfurt".to_owned(), chrono_tz::Europe::Berlin));
+		return Ok(Some(Place {
+			name,
+			label: timezone.name().to_owned(),
+			zone: Zone::Named(timezone),
+		}));
+	}
+	if let Some(offset) = parse_offset_zone(place) {
+		return match offset {
+			Ok(zone) => Ok(Some(Place {
+				label: zone.offset.to_string(),
+				name: zone.name,
+				zone: Zone::Fixed(zone.offset),
+			})),
+			Err(err) => {
+				let text = format!("Could not understand the offset {place}: {err}");
+				event.reply().content(&text).await?;
+				Ok(None)
+			}
+		};
+	}
+	match find_by_name(place).await {
+		Some(entry) => Ok(Some(Place {
+			name: entry.display_name(),
+			label: entry.timezone.name().to_owned(),
+			zone: Zone::Named(entry.timezone),
+		})),
+		None => {
+			let text = format!("Could not find a place called {place}.");
+			event.reply().content(&text).await?;
+			Ok(None)
+		}
+	}
+}
+
+/// Resolve a time expression in a place, replying with an error if that time does not exist there.
+async fn resolve_time(
+	event: &EventWithContext<&MessageCreate>,
+	expression: TimeExpression,
+	place: &Place,
+) -> eyre::Result<Option<DateTime<FixedOffset>>> {
+	let local = expression.resolve(place.zone.today());
+	match place.zone.localize(&local) {
+		LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => Ok(Some(time)),
+		LocalResult::None => {
+			let text = format!(
+				"{} does not exist in {}, the clocks skip over it.",
+				local.format("%H:%M on %d.%m.%Y"),
+				place.name
+			);
+			event.reply().content(&text).await?;
+			Ok(None)
+		}
+	}
+}
+
+/// Convert a time in one place into another, like `!time 18:30 berlin to tokyo`.
+async fn convert_time(
+	event: EventWithContext<&MessageCreate>,
+	expression: TimeExpression,
+	places: &str,
+) -> eyre::Result<()> {
+	let (source, destination) = match places.split_once(" to ") {
+		Some((source, destination)) => (source.trim(), Some(destination.trim())),
+		None => match places.strip_prefix("to ") {
+			Some(destination) => ("", Some(destination.trim())),
+			None => (places.trim(), None),
+		},
+	};
+	let Some(source) = resolve_place(&event, source).await? else {
+		return Ok(());
+	};
+	let destination = match destination {
+		Some(destination) => match resolve_place(&event, destination).await? {
+			Some(destination) => Some(destination),
+			None => return Ok(()),
+		},
+		None => None,
+	};
+	let Some(time) = resolve_time(&event, expression, &source).await? else {
+		return Ok(());
+	};
+
+	let mut text = format!(
+		"**{}** in {} ({})",
+		time.format("%H:%M on %d.%m.%Y"),
+		source.name,
+		source.label
+	);
+	if let Some(destination) = destination {
+		let converted = destination.zone.convert(time);
+		text += &format!(
+			" is **{}** in {} ({})",
+			converted.format("%H:%M on %d.%m.%Y"),
+			destination.name,
+			destination.label
+		);
+	}
+	let timestamp = time.timestamp();
+	text += &format!("\nFor you that is <t:{timestamp}:F> (<t:{timestamp}:R>)");
+	event.reply().content(&text).await?;
+	Ok(())
+}
+
+/// Post the local time of someone who saved their timezone. Returns whether they had saved one.
+async fn post_saved_time(
+	event: &EventWithContext<&MessageCreate>,
+	user: Id<UserMarker>,
+) -> eyre::Result<bool> {
+	let zone = zone_book().await?.users.get(&user).cloned();
+	let Some((place, timezone)) = zone.and_then(|it| Some((it.place.clone(), it.timezone()?)))
+	else {
+		return Ok(false);
+	};
+	let text = format_timezone(&format!("<@{user}> in {place}"), &timezone, &place);
+	event.reply().content(&text).await?;
+	Ok(true)
+}
+
+async fn post_time(
+	event: EventWithContext<&MessageCreate>,
+	search_phrase: &str,
+) -> eyre::Result<()> {
+	let message: Option<String> = if let Some(offset) = parse_offset_zone(search_phrase) {
+		match offset {
+			Ok(zone) => Some(format_timezone(&zone.name, &zone.offset, &zone.name)),
+			Err(err) => Some(format!(
+				"Could not understand the offset {search_phrase}: {err}"
+			)),
+		}
+	} else {
+		let places = find_places(search_phrase).await;
+		places.first().map(|it| {
+			let mut text = format_timezone(&it.display_name(), &it.timezone, &it.name);
+			if let Some(note) = ambiguity_note(&places, "!time") {
+				text += &note;
+			}
+			text
+		})
+	};
+
+	if let Some(text) = message {
+		event.reply().content(&text).await?;
+	}
+
+	Ok(())
+}
+
+/// All places matching a search, the most populous first.
+async fn find_places(search_phrase: &str) -> Vec<&'static GeoEntry> {
+	let query = PlaceQuery::new(search_phrase);
+	let mut places = geo_database().await.search(&query);
+	places.sort_by_key(|it| std::cmp::Reverse(it.population));
+	places
+}
+
+async fn find_by_name(search_phrase: &str) -> Option<&'static GeoEntry> {
+	find_places(search_phrase).await.into_iter().next()
+}
+
+/// How many places to suggest when a search is ambiguous.
+const MAX_CANDIDATES: usize = 5;
+
+/// List the other places a search might have meant, biggest first, with their region and country.
+fn ambiguity_note(places: &[&GeoEntry], command: &str) -> Option<String> {
+	let candidates = places.get(1..)?;
+	let candidates = &candidates[..candidates.len().min(MAX_CANDIDATES)];
+	let example = candidates.first()?;
+	let candidates = candidates
+		.iter()
+		.map(|it| format!("**{}** ({})", it.display_name(), it.timezone.name()))
+		.collect::<Vec<_>>()
+		.join(", ");
+	let qualifier = [&example.admin1, &example.country]
+		.into_iter()
+		.find(|it| !it.is_empty())
+		.map(|it| it.to_lowercase())
+		.unwrap_or_default();
+	Some(format!(
+		"-# Did you mean {candidates}? Add the region or country, like `{command} {}, {qualifier}`.\n",
+		example.name.to_lowercase()
+	))
+}
+
+fn format_timezone(name: &str, timezone: &impl TimeZone, timezone_label: &str) -> String {
+	let current_time_in_timezone = Utc::now().with_timezone(timezone);
+	let mut f = format!(
+		"**{}** - [{}](<http://time.is/{}>)\nCurrent Time: {:02}.{:02}.{:04} **{:02}:{:02}**\n",
+		name,
+		timezone_label,
+		urlencoding::encode(timezone_label),
+		current_time_in_timezone.day(),
+		current_time_in_timezone.month(),
+		current_time_in_timezone.year(),
+		current_time_in_timezone.hour(),
+		current_time_in_timezone.minute(),
+	);
+	f += &match current_time_in_timezone.with_time(NaiveTime::MIN) {
+			LocalResult::Single(timestamp) => {
+				format!("-# Their midnight is your <t:{}:t>\n", timestamp.timestamp())
+			}
+			LocalResult::Ambiguous(a, b) => format!(
+				"-# Their midnights (??? i thought timezone switches happened only at like 02:00, not midnight) are <t:{}:t> and <t:{}:t>\n",
+				a.timestamp(),
+				b.timestamp()
+			),
+			LocalResult::None => {
+				"-# They don't have a midnight (??? i thought timezone switches happened only at like 02:00, not midnight)\n".to_owned()
+			}
+		};
+	 f
+}
+
+#[derive(Clone, Debug)]
+struct MultiNamePrefixMatcher {
+	expected_matches: Vec<Arc<str>>,
+}
+
+impl MultiNamePrefixMatcher {
+	fn new(text: &str) -> MultiNamePrefixMatcher {
+		MultiNamePrefixMatcher {
+			expected_matches: text
+				.split_whitespace()
+				.filter(|it| !it.is_empty())
+				.map(|it| UniCase::new(it).to_folded_case().into())
+				.collect(),
+		}
+	}
+
+	const fn is_matched(&self) -> bool {
+		self.expected_matches.is_empty()
+	}
+
+	fn accept_casefolded_match(&mut self, obj: &str) {
+		for ele in obj.split_whitespace() {
+			self.expected_matches
+				.retain(|it| !ele.starts_with(it.as_ref()));
+		}
+	}
+}
+
+/// A place search like `paris texas` or `springfield, illinois`. Words after a comma only match the region or
+/// country, other words match the place name and may match its region or country as long as one of them matches
+/// the name.
+struct PlaceQuery {
+	name: MultiNamePrefixMatcher,
+	qualifier: MultiNamePrefixMatcher,
+}
+
+impl PlaceQuery {
+	fn new(text: &str) -> PlaceQuery {
+		let (name, qualifier) = text.split_once(',').unwrap_or((text, ""));
+		PlaceQuery {
+			name: MultiNamePrefixMatcher::new(name),
+			qualifier: MultiNamePrefixMatcher::new(qualifier),
+		}
+	}
+
+	/// Every word of the query, each of which has to start a word of a matching place.
+	fn words(&self) -> impl Iterator<Item = &str> {
+		self.name
+			.expected_matches
+			.iter()
+			.chain(&self.qualifier.expected_matches)
+			.map(|it| &**it)
+	}
+
+	fn matches(&self, entry: &GeoEntry) -> bool {
+		let mut name = self.name.clone();
+		for ele in &entry.match_names {
+			name.accept_casefolded_match(ele);
+		}
+		if name.expected_matches.len() == self.name.expected_matches.len() {
+			return false;
+		}
+		let mut qualifier = self.qualifier.clone();
+		for ele in &entry.qualifier_names {
+			name.accept_casefolded_match(ele);
+			qualifier.accept_casefolded_match(ele);
+		}
+		name.is_matched() && qualifier.is_matched()
+	}
+}
+
+#[derive(Clone, Debug)]
+#[allow(unused)]
+struct GeoEntry {
+	geonameid: usize,
+	name: String,
+	alternatenames: Vec<String>,
+	timezone: Tz,
+	match_names: Vec<String>,
+	/// The ISO code of the country, like `US`.
+	country_code: String,
+	country: String,
+	/// The name of the state or region, like `Texas`.
+	admin1: String,
+	/// Folded names of the country and region, to search with.
+	qualifier_names: Vec<String>,
+	population: u32,
+	latitude: f32,
+	longitude: f32,
+}
+
+impl GeoEntry {
+	/// Whether this is an actual place, rather than a timezone without a location.
+	const fn is_place(&self) -> bool {
+		!self.latitude.is_nan() && !self.longitude.is_nan()
+	}
+
+	/// The name with its region and country, like `Springfield, Illinois, United States`.
+	fn display_name(&self) -> String {
+		[&self.name, &self.admin1, &self.country]
+			.into_iter()
+			.filter(|it| !it.is_empty())
+			.map(String::as_str)
+			.collect::<Vec<_>>()
+			.join(", ")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono_tz::{America, Europe, Tz};
+	use unicase::UniCase;
+
+	use crate::features::time::{GeoEntry, PlaceQuery, ambiguity_note};
+
+	fn entry(name: &str, admin1: &str, country: &str, timezone: Tz, population: u32) -> GeoEntry {
+		GeoEntry {
+			geonameid: 0,
+			name: name.to_owned(),
+			alternatenames: vec![],
+			timezone,
+			match_names: vec![UniCase::new(name).to_folded_case()],
+			country_code: String::new(),
+			country: country.to_owned(),
+			admin1: admin1.to_owned(),
+			qualifier_names: [admin1, country]
+				.map(|it| UniCase::new(it).to_folded_case())
+				.to_vec(),
+			population,
+			latitude: 0.0,
+			longitude: 0.0,
+		}
+	}
+
+	#[test]
+	fn test_place_queries() {
+		let paris = entry("Paris", "Île-de-France", "France", Europe::Paris, 2_138_551);
+		let paris_texas = entry("Paris", "Texas", "United States", America::Chicago, 24_171);
+		let matches = |query, entry| PlaceQuery::new(query).matches(entry);
+
+		assert!(matches("paris", &paris));
+		assert!(matches("paris texas", &paris_texas));
+		assert!(!matches("paris texas", &paris));
+		assert!(matches("paris, united states", &paris_texas));
+		assert!(!matches("texas", &paris_texas));
+		assert!(!matches("paris, paris", &paris));
+		let note = ambiguity_note(&[&paris, &paris_texas], "!time").unwrap();
+		assert!(note.contains("**Paris, Texas, United States** (America/Chicago)"));
+		assert!(ambiguity_note(&[&paris], "!time").is_none());
+
+		let missouri = entry(
+			"Springfield",
+			"Missouri",
+			"United States",
+			America::Chicago,
+			169_176,
+		);
+		let massachusetts = entry(
+			"Springfield",
+			"Massachusetts",
+			"United States",
+			America::New_York,
+			155_929,
+		);
+		let illinois = entry(
+			"Springfield",
+			"Illinois",
+			"United States",
+			America::Chicago,
+			114_394,
+		);
+		let note = ambiguity_note(&[&missouri, &massachusetts, &illinois], "!time").unwrap();
+		assert!(note.contains("**Springfield, Massachusetts, United States** (America/New_York)"));
+		assert!(note.contains("**Springfield, Illinois, United States** (America/Chicago)"));
+		assert!(note.contains("`!time springfield, massachusetts`"));
+	}
+}
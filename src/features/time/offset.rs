@@ -0,0 +1,284 @@
+use chrono::{FixedOffset, Offset, TimeZone, Utc};
+use chrono_tz::TZ_VARIANTS;
+use eyre::{bail, eyre};
+
+/// ISO 8601 does not allow offsets beyond this, and no place on earth comes close.
+const MAX_OFFSET_HOURS: i32 = 18;
+
+type Abbreviation = (&'static str, &'static [(&'static str, i32)]);
+
+/// Common timezone abbreviations and what they may stand for, with offsets in minutes.
+const ABBREVIATIONS: &[Abbreviation] = &[
+	("UTC", &[("Coordinated Universal Time", 0)]),
+	("GMT", &[("Greenwich Mean Time", 0)]),
+	("WET", &[("Western European Time", 0)]),
+	("WEST", &[("Western European Summer Time", 60)]),
+	("CET", &[("Central European Time", 60)]),
+	("CEST", &[("Central European Summer Time", 120)]),
+	("EET", &[("Eastern European Time", 120)]),
+	("EEST", &[("Eastern European Summer Time", 180)]),
+	("MSK", &[("Moscow Time", 180)]),
+	(
+		"BST",
+		&[
+			("British Summer Time", 60),
+			("Bangladesh Standard Time", 360),
+		],
+	),
+	(
+		"IST",
+		&[
+			("India Standard Time", 330),
+			("Irish Standard Time", 60),
+			("Israel Standard Time", 120),
+		],
+	),
+	("WAT", &[("West Africa Time", 60)]),
+	("CAT", &[("Central Africa Time", 120)]),
+	("SAST", &[("South Africa Standard Time", 120)]),
+	("EAT", &[("East Africa Time", 180)]),
+	(
+		"AST",
+		&[
+			("Atlantic Standard Time", -240),
+			("Arabia Standard Time", 180),
+		],
+	),
+	(
+		"GST",
+		&[("Gulf Standard Time", 240), ("South Georgia Time", -120)],
+	),
+	("PKT", &[("Pakistan Standard Time", 300)]),
+	("NPT", &[("Nepal Time", 345)]),
+	("ICT", &[("Indochina Time", 420)]),
+	("WIB", &[("Western Indonesia Time", 420)]),
+	(
+		"CST",
+		&[
+			("Central Standard Time", -360),
+			("China Standard Time", 480),
+			("Cuba Standard Time", -300),
+		],
+	),
+	("HKT", &[("Hong Kong Time", 480)]),
+	("SGT", &[("Singapore Time", 480)]),
+	("AWST", &[("Australian Western Standard Time", 480)]),
+	("JST", &[("Japan Standard Time", 540)]),
+	("KST", &[("Korea Standard Time", 540)]),
+	("ACST", &[("Australian Central Standard Time", 570)]),
+	("ACDT", &[("Australian Central Daylight Time", 630)]),
+	("AEST", &[("Australian Eastern Standard Time", 600)]),
+	("AEDT", &[("Australian Eastern Daylight Time", 660)]),
+	("NZST", &[("New Zealand Standard Time", 720)]),
+	("NZDT", &[("New Zealand Daylight Time", 780)]),
+	("HST", &[("Hawaii Standard Time", -600)]),
+	("AKST", &[("Alaska Standard Time", -540)]),
+	("AKDT", &[("Alaska Daylight Time", -480)]),
+	("PST", &[("Pacific Standard Time", -480)]),
+	("PDT", &[("Pacific Daylight Time", -420)]),
+	("MST", &[("Mountain Standard Time", -420)]),
+	("MDT", &[("Mountain Daylight Time", -360)]),
+	("CDT", &[("Central Daylight Time", -300)]),
+	("EST", &[("Eastern Standard Time", -300)]),
+	("EDT", &[("Eastern Daylight Time", -240)]),
+	("ADT", &[("Atlantic Daylight Time", -180)]),
+	("NST", &[("Newfoundland Standard Time", -210)]),
+	("NDT", &[("Newfoundland Daylight Time", -150)]),
+	("BRT", &[("Brasília Time", -180)]),
+	("ART", &[("Argentina Time", -180)]),
+];
+
+/// A fixed offset from UTC, like `UTC-5`, `GMT+5:45` or `EST`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OffsetZone {
+	/// The offset written out the way it was asked for, like `GMT-3:30`.
+	pub name: String,
+	pub offset: FixedOffset,
+}
+
+/// Format minutes east of UTC like `UTC+5:45`.
+fn format_minutes(minutes: i32) -> String {
+	let sign = if minutes < 0 { '-' } else { '+' };
+	let (hours, minutes) = (minutes.abs() / 60, minutes.abs() % 60);
+	match minutes {
+		0 => format!("UTC{sign}{hours}"),
+		_ => format!("UTC{sign}{hours}:{minutes:02}"),
+	}
+}
+
+/// Find an abbreviation. Many of them are also words, like `CAT` or `ART`, so they have to be written in upper case,
+/// except for UTC and GMT.
+fn find_abbreviation(base: &str) -> Option<&'static Abbreviation> {
+	ABBREVIATIONS.iter().find(|(it, _)| {
+		*it == base || (matches!(*it, "UTC" | "GMT") && it.eq_ignore_ascii_case(base))
+	})
+}
+
+/// The offset of a timezone abbreviation or name in minutes, or `None` if it is neither.
+fn base_minutes(base: &str) -> Option<eyre::Result<(String, i32)>> {
+	if base.is_empty() {
+		return Some(Ok(("UTC".to_owned(), 0)));
+	}
+	if let Some((abbreviation, meanings)) = find_abbreviation(base) {
+		return Some(match meanings {
+			[(_, minutes)] => Ok(((*abbreviation).to_owned(), *minutes)),
+			_ => {
+				let meanings = meanings
+					.iter()
+					.map(|(name, minutes)| format!("{name} ({})", format_minutes(*minutes)))
+					.collect::<Vec<_>>()
+					.join(", ");
+				Err(eyre!(
+					"{abbreviation} is ambiguous, it could mean {meanings}. Use an offset like UTC+2 instead."
+				))
+			}
+		});
+	}
+	// Whole timezones like `Europe/Berlin+1` are offset from whatever they are right now
+	let tz = TZ_VARIANTS
+		.iter()
+		.find(|it| it.name().eq_ignore_ascii_case(base))?;
+	let offset = tz.offset_from_utc_datetime(&Utc::now().naive_utc()).fix();
+	Some(Ok((tz.name().to_owned(), offset.local_minus_utc() / 60)))
+}
+
+/// Parse `5`, `05`, `5:45` or `0545` into minutes.
+fn parse_amount(amount: &str) -> Option<eyre::Result<i32>> {
+	let (hours, minutes) = match amount.split_once(':') {
+		Some((hours, minutes)) => (hours, minutes),
+		None if amount.len() > 2 => amount.split_at(amount.len() - 2),
+		None => (amount, "0"),
+	};
+	let is_number =
+		|it: &str| !it.is_empty() && it.len() <= 2 && it.bytes().all(|it| it.is_ascii_digit());
+	if !is_number(hours) || !is_number(minutes) {
+		return None;
+	}
+	let (hours, minutes): (i32, i32) = (hours.parse().ok()?, minutes.parse().ok()?);
+	Some(match minutes {
+		0..60 => Ok(hours * 60 + minutes),
+		_ => Err(eyre!("{minutes} minutes is not below 60.")),
+	})
+}
+
+/// Parse `TZ±hours[:minutes]` or a timezone abbreviation on its own, like `UTC-5`, `GMT+5:45`, `CET+1` or `EST`.
+/// Returns `None` if the phrase is not an offset at all, so `Winston-Salem` is left to the place search.
+pub fn parse_offset_zone(phrase: &str) -> Option<eyre::Result<OffsetZone>> {
+	let phrase = phrase
+		.split_whitespace()
+		.collect::<String>()
+		.replace('−', "-");
+	// Whole timezone names are found by the place search already. Some have signs of their own, like `Etc/GMT-3`,
+	// which must not be read as an offset.
+	if phrase.contains(['+', '-'])
+		&& TZ_VARIANTS
+			.iter()
+			.any(|it| it.name().eq_ignore_ascii_case(&phrase))
+	{
+		return None;
+	}
+	// The offset comes last, so `Etc/GMT-3+1` is one hour ahead of `Etc/GMT-3`
+	let Some(sign_index) = phrase.rfind(['+', '-']) else {
+		find_abbreviation(&phrase)?;
+		return Some(base_minutes(&phrase)?.and_then(|(name, minutes)| {
+			let offset =
+				FixedOffset::east_opt(minutes * 60).ok_or_else(|| eyre!("Offset out of range."))?;
+			Ok(OffsetZone { name, offset })
+		}));
+	};
+	let (base, amount) = phrase.split_at(sign_index);
+	let (sign, amount) = amount.split_at(1);
+	let amount = parse_amount(amount)?;
+	let parse = || {
+		let Some(base) = base_minutes(base) else {
+			bail!("{base} is not a timezone I know.");
+		};
+		let (base, base_offset) = base?;
+		let amount = amount?;
+		let minutes = match sign {
+			"-" => base_offset - amount,
+			_ => base_offset + amount,
+		};
+		if minutes.abs() > MAX_OFFSET_HOURS * 60 {
+			bail!(
+				"{} is too far from UTC, offsets go up to {MAX_OFFSET_HOURS} hours.",
+				format_minutes(minutes)
+			);
+		}
+		let offset =
+			FixedOffset::east_opt(minutes * 60).ok_or_else(|| eyre!("Offset out of range."))?;
+		let (hours, rest) = (amount / 60, amount % 60);
+		let name = match rest {
+			0 => format!("{base}{sign}{hours}"),
+			_ => format!("{base}{sign}{hours}:{rest:02}"),
+		};
+		Ok(OffsetZone { name, offset })
+	};
+	Some(parse())
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::FixedOffset;
+
+	use crate::features::time::offset::{base_minutes, parse_offset_zone};
+
+	#[test]
+	fn test_offsets() {
+		let parse = |phrase| parse_offset_zone(phrase).map(|it| it.map_err(|err| err.to_string()));
+		let minutes = |phrase| parse(phrase).unwrap().unwrap().offset.local_minus_utc() / 60;
+
+		assert_eq!(minutes("UTC-5"), -300);
+		assert_eq!(minutes("utc+2"), 120);
+		assert_eq!(minutes("GMT-03:30"), -210);
+		assert_eq!(minutes("UTC+5:45"), 345);
+		assert_eq!(minutes("UTC+0545"), 345);
+		assert_eq!(minutes("EST"), -300);
+		assert_eq!(minutes("EST+1"), -240);
+		assert_eq!(minutes("+9"), 540);
+		assert_eq!(minutes("UTC − 4"), -240);
+		assert_eq!(
+			parse("GMT-03:30").unwrap().unwrap().offset,
+			FixedOffset::west_opt(210 * 60).unwrap()
+		);
+		assert_eq!(parse("GMT-03:30").unwrap().unwrap().name, "GMT-3:30");
+
+		assert!(
+			parse("CST")
+				.unwrap()
+				.unwrap_err()
+				.contains("China Standard Time (UTC+8)")
+		);
+		assert!(parse("IST+1").unwrap().unwrap_err().contains("ambiguous"));
+		assert!(parse("UTC+5:75").unwrap().is_err());
+		assert!(parse("UTC+19").unwrap().is_err());
+		assert!(parse("Narnia+2").unwrap().is_err());
+
+		assert_eq!(parse("Winston-Salem"), None);
+		assert_eq!(parse("new york"), None);
+		assert_eq!(parse("Europe/Berlin"), None);
+		assert_eq!(parse("cat"), None);
+		assert_eq!(parse("Art"), None);
+		assert_eq!(minutes("CAT"), 120);
+		assert!(parse("eat+1").unwrap().is_err());
+	}
+
+	#[test]
+	fn test_etc_zones() {
+		let minutes = |phrase| {
+			parse_offset_zone(phrase)
+				.unwrap()
+				.unwrap()
+				.offset
+				.local_minus_utc()
+				/ 60
+		};
+		// POSIX style names have their sign inverted, Etc/GMT-3 is three hours ahead of UTC
+		assert_eq!(base_minutes("Etc/GMT-3").unwrap().unwrap().1, 180);
+		assert_eq!(base_minutes("etc/gmt+10").unwrap().unwrap().1, -600);
+		assert_eq!(minutes("Etc/GMT-3+1"), 240);
+		assert_eq!(minutes("Etc/GMT+10-0:30"), -630);
+		assert!(parse_offset_zone("Etc/GMT-3").is_none());
+		assert!(parse_offset_zone("Etc/GMT+10").is_none());
+	}
+}
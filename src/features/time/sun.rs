@@ -0,0 +1,263 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
+use twilight_model::gateway::payload::incoming::MessageCreate;
+
+use super::{
+	GeoEntry, ambiguity_note,
+	expr::{self, DateSpec},
+	find_places,
+	geonames::geo_database,
+	zones::zone_book,
+};
+use crate::{EventWithContext, claim_command, handle_message};
+
+handle_message!(should_reply, on_sun);
+claim_command!("sun");
+
+/// The zenith angle of the sun's centre at sunrise and sunset, which accounts for refraction and the sun's radius.
+const SUNRISE_ZENITH: f64 = 90.833;
+
+/// Whether and when the sun crosses the horizon on a day.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Daylight {
+	Normal {
+		sunrise: DateTime<Utc>,
+		sunset: DateTime<Utc>,
+	},
+	/// The sun stays above the horizon all day.
+	PolarDay,
+	/// The sun stays below the horizon all day.
+	PolarNight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SunTimes {
+	pub noon: DateTime<Utc>,
+	pub daylight: Daylight,
+}
+
+/// The sun's declination in radians and the equation of time in minutes at a Julian day, as in NOAA's solar
+/// calculator.
+fn solar_position(julian_day: f64) -> (f64, f64) {
+	let t = (julian_day - 2_451_545.0) / 36525.0;
+	let mean_longitude = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0);
+	let mean_anomaly = (357.52911 + t * (35999.05029 - 0.0001537 * t)).to_radians();
+	let eccentricity = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+	let center = mean_anomaly.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+		+ (2.0 * mean_anomaly).sin() * (0.019993 - 0.000101 * t)
+		+ (3.0 * mean_anomaly).sin() * 0.000289;
+	let omega = (125.04 - 1934.136 * t).to_radians();
+	let apparent_longitude =
+		(mean_longitude + center - 0.00569 - 0.00478 * omega.sin()).to_radians();
+	let mean_obliquity =
+		23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+	let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+	let declination = (obliquity.sin() * apparent_longitude.sin()).asin();
+
+	let (l, m, e) = (mean_longitude.to_radians(), mean_anomaly, eccentricity);
+	let y = (obliquity / 2.0).tan().powi(2);
+	let equation_of_time = 4.0
+		* (y * (2.0 * l).sin() - 2.0 * e * m.sin() + 4.0 * e * y * m.sin() * (2.0 * l).cos()
+			- 0.5 * y * y * (4.0 * l).sin()
+			- 1.25 * e * e * (2.0 * m).sin())
+		.to_degrees();
+	(declination, equation_of_time)
+}
+
+/// Sunrise, solar noon and sunset on a date at a place, accurate to about a minute away from the poles.
+pub fn sun_times(date: NaiveDate, latitude: f64, longitude: f64) -> SunTimes {
+	let midnight = date.and_time(NaiveTime::MIN).and_utc();
+	let julian_midnight = midnight.timestamp() as f64 / 86400.0 + 2_440_587.5;
+	// Minutes after midnight UTC, starting at local mean noon and then refined at the solar noon itself
+	let mut noon = 720.0 - 4.0 * longitude;
+	let mut declination = 0.0;
+	for _ in 0..2 {
+		let (position_declination, equation_of_time) =
+			solar_position(julian_midnight + noon / 1440.0);
+		noon = 720.0 - 4.0 * longitude - equation_of_time;
+		declination = position_declination;
+	}
+	let at = |minutes: f64| midnight + TimeDelta::seconds((minutes * 60.0).round() as i64);
+
+	let latitude = latitude.to_radians();
+	let cos_hour_angle = SUNRISE_ZENITH.to_radians().cos() / (latitude.cos() * declination.cos())
+		- latitude.tan() * declination.tan();
+	let daylight = if cos_hour_angle > 1.0 {
+		Daylight::PolarNight
+	} else if cos_hour_angle < -1.0 {
+		Daylight::PolarDay
+	} else {
+		// Every degree the earth turns takes four minutes
+		let half_day = 4.0 * cos_hour_angle.acos().to_degrees();
+		Daylight::Normal {
+			sunrise: at(noon - half_day),
+			sunset: at(noon + half_day),
+		}
+	};
+	SunTimes {
+		noon: at(noon),
+		daylight,
+	}
+}
+
+/// Split an optional date off the end of a query, like `tromsø tomorrow`.
+fn split_date(query: &str) -> (&str, Option<DateSpec>) {
+	let query = query.trim();
+	let (place, last) = query.rsplit_once(' ').unwrap_or(("", query));
+	match expr::parse_date(&last.to_lowercase(), Utc::now().date_naive()) {
+		Some(date) => (place.trim(), Some(date)),
+		None => (query, None),
+	}
+}
+
+/// Find a place with coordinates, replying with an error if there is none. An empty place means the author's own
+/// saved place, or Frankfurt if they have none.
+async fn find_located_place(
+	event: &EventWithContext<&MessageCreate>,
+	place: &str,
+) -> eyre::Result<Option<(&'static GeoEntry, Option<String>)>> {
+	let place = match place {
+		"" => {
+			let saved = zone_book().await?.users.get(&event.author.id).cloned();
+			let Some(saved) = saved else {
+				return find_searched_place(event, "frankfurt").await;
+			};
+			let database = geo_database().await;
+			if let Some(entry) = saved.geonameid.and_then(|it| database.get(it)) {
+				return Ok(Some((entry, None)));
+			}
+			// Saved before ids were, so it has to be searched for again
+			saved.place
+		}
+		place => place.to_owned(),
+	};
+	find_searched_place(event, &place).await
+}
+
+async fn find_searched_place(
+	event: &EventWithContext<&MessageCreate>,
+	place: &str,
+) -> eyre::Result<Option<(&'static GeoEntry, Option<String>)>> {
+	let places = find_places(place).await;
+	let Some(&entry) = places.first() else {
+		let text = format!("Could not find a place called {place}.");
+		event.reply().content(&text).await?;
+		return Ok(None);
+	};
+	if !entry.is_place() {
+		let text = format!(
+			"{} is a timezone, not a place. Try a city in it instead, like `!sun berlin`. Save a city with `!time set` to make it your default.",
+			entry.timezone.name()
+		);
+		event.reply().content(&text).await?;
+		return Ok(None);
+	}
+	// Timezones have no sunrise, but every other place does, even in the same timezone
+	let candidates = places
+		.into_iter()
+		.filter(|it| it.is_place())
+		.collect::<Vec<_>>();
+	Ok(Some((entry, ambiguity_note(&candidates, "!sun"))))
+}
+
+fn format_duration(duration: TimeDelta) -> String {
+	let minutes = duration.num_minutes();
+	format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+async fn on_sun(event: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
+	let query = match event.content.strip_prefix("!sun") {
+		Some(query) if query.is_empty() || query.starts_with(' ') => query,
+		_ => return Ok(()),
+	};
+	let (place, date) = split_date(query);
+	let Some((entry, note)) = find_located_place(&event, place).await? else {
+		return Ok(());
+	};
+	let today = Utc::now().with_timezone(&entry.timezone).date_naive();
+	let date = date.map_or(today, |it| it.resolve(today));
+	let times = sun_times(date, entry.latitude.into(), entry.longitude.into());
+
+	let line = |label: &str, time: DateTime<Utc>| {
+		format!(
+			"{label}: **{}** (<t:{}:t>)\n",
+			time.with_timezone(&entry.timezone).format("%H:%M"),
+			time.timestamp()
+		)
+	};
+	let mut text = format!(
+		"**{}** on {} ({})\n",
+		entry.display_name(),
+		date.format("%A, %d.%m.%Y"),
+		entry.timezone.name()
+	);
+	match times.daylight {
+		Daylight::Normal { sunrise, sunset } => {
+			text += &line("Sunrise", sunrise);
+			text += &line("Solar noon", times.noon);
+			text += &line("Sunset", sunset);
+			text += &format!("Day length: **{}**\n", format_duration(sunset - sunrise));
+		}
+		Daylight::PolarDay => {
+			text += "The sun does not set all day (polar day).\n";
+			text += &line("Highest at", times.noon);
+		}
+		Daylight::PolarNight => {
+			text += "The sun does not rise all day (polar night).\n";
+			text += &line("Closest to rising at", times.noon);
+		}
+	}
+	if let Some(note) = note {
+		text += &note;
+	}
+	event.reply().content(&text).await?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+
+	use crate::features::time::sun::{Daylight, sun_times};
+
+	fn close(actual: DateTime<Utc>, expected: &str) {
+		let expected = DateTime::parse_from_rfc3339(expected).unwrap();
+		let difference = (actual - expected.to_utc()).abs();
+		assert!(
+			difference <= TimeDelta::minutes(2),
+			"{actual} is not {expected}"
+		);
+	}
+
+	#[test]
+	fn test_sun_times() {
+		let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+
+		// Berlin at the summer solstice
+		let berlin = sun_times(date(2024, 6, 21), 52.52, 13.405);
+		close(berlin.noon, "2024-06-21T13:07:00+02:00");
+		let Daylight::Normal { sunrise, sunset } = berlin.daylight else {
+			panic!("{berlin:?}");
+		};
+		close(sunrise, "2024-06-21T04:43:00+02:00");
+		close(sunset, "2024-06-21T21:33:00+02:00");
+
+		// Sydney, where the day crosses midnight UTC
+		let Daylight::Normal { sunrise, sunset } =
+			sun_times(date(2024, 12, 21), -33.8688, 151.2093).daylight
+		else {
+			panic!();
+		};
+		close(sunrise, "2024-12-21T05:41:00+11:00");
+		close(sunset, "2024-12-21T20:06:00+11:00");
+
+		// Tromsø, north of the arctic circle
+		assert_eq!(
+			sun_times(date(2024, 6, 21), 69.6496, 18.956).daylight,
+			Daylight::PolarDay
+		);
+		assert_eq!(
+			sun_times(date(2024, 12, 21), 69.6496, 18.956).daylight,
+			Daylight::PolarNight
+		);
+	}
+}
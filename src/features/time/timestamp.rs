@@ -0,0 +1,97 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use twilight_model::gateway::payload::incoming::MessageCreate;
+
+use super::{expr, resolve_place, resolve_time};
+use crate::{EventWithContext, claim_command, handle_message};
+
+handle_message!(should_reply, on_timestamp);
+claim_command!("timestamp");
+
+/// Every way Discord can format a `<t:...>` timestamp.
+const STYLES: [(char, &str); 7] = [
+	('t', "short time"),
+	('T', "long time"),
+	('d', "short date"),
+	('D', "long date"),
+	('f', "short date and time"),
+	('F', "long date and time"),
+	('R', "relative"),
+];
+
+/// Parse `now` or `in <amount> <unit>`, like `in 2 hours`.
+fn parse_relative(text: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+	let text = text.trim().to_lowercase();
+	if text == "now" {
+		return Some(now);
+	}
+	let (amount, unit) = text.strip_prefix("in ")?.split_once(' ')?;
+	let amount = amount.parse().ok()?;
+	let delta = match unit.trim().trim_end_matches('s') {
+		"second" | "sec" => TimeDelta::try_seconds(amount)?,
+		"minute" | "min" => TimeDelta::try_minutes(amount)?,
+		"hour" => TimeDelta::try_hours(amount)?,
+		"day" => TimeDelta::try_days(amount)?,
+		"week" => TimeDelta::try_weeks(amount)?,
+		_ => return None,
+	};
+	now.checked_add_signed(delta)
+}
+
+async fn on_timestamp(event: EventWithContext<&MessageCreate>) -> eyre::Result<()> {
+	let Some(rest) = event.content.strip_prefix("!timestamp ") else {
+		return Ok(());
+	};
+	let now = Utc::now();
+	let time = match parse_relative(rest, now) {
+		Some(time) => time.fixed_offset(),
+		None => {
+			let Some((expression, place)) = expr::parse_expression(rest, now.date_naive()) else {
+				event
+					.reply()
+					.content(
+						"use: !timestamp <time> [in <place>], like `!timestamp tomorrow 18:00 in berlin`",
+					)
+					.await?;
+				return Ok(());
+			};
+			let place = place.strip_prefix("in ").unwrap_or(&place).trim();
+			let Some(place) = resolve_place(&event, place).await? else {
+				return Ok(());
+			};
+			let Some(time) = resolve_time(&event, expression, &place).await? else {
+				return Ok(());
+			};
+			time
+		}
+	};
+	let timestamp = time.timestamp();
+	let mut text = format!("<t:{timestamp}:F>\n");
+	for (style, description) in STYLES {
+		text += &format!("`<t:{timestamp}:{style}>` {description}: <t:{timestamp}:{style}>\n");
+	}
+	event.reply().content(&text).await?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{TimeDelta, Utc};
+
+	use crate::features::time::timestamp::parse_relative;
+
+	#[test]
+	fn test_relative_times() {
+		let now = Utc::now();
+		assert_eq!(parse_relative("now", now), Some(now));
+		assert_eq!(
+			parse_relative("in 2 hours", now),
+			Some(now + TimeDelta::hours(2))
+		);
+		assert_eq!(
+			parse_relative("In 1 Week", now),
+			Some(now + TimeDelta::weeks(1))
+		);
+		assert_eq!(parse_relative("in 2 fortnights", now), None);
+		assert_eq!(parse_relative("18:00 in berlin", now), None);
+	}
+}
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+use twilight_model::id::{Id, marker::UserMarker};
+
+use crate::utils::persist;
+
+const ZONES_PATH: &str = "time_zones.json";
+
+/// The place someone saved with `!time set`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedZone {
+	pub place: String,
+	/// The IANA name of the timezone, like `Europe/Berlin`.
+	pub timezone: String,
+	/// The geonames id of the place, so it can be found again exactly. `None` if a timezone was saved instead of a
+	/// place, or the place was saved before ids were.
+	#[serde(default)]
+	pub geonameid: Option<usize>,
+}
+
+impl SavedZone {
+	pub fn timezone(&self) -> Option<Tz> {
+		self.timezone.parse().ok()
+	}
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ZoneBook {
+	pub users: HashMap<Id<UserMarker>, SavedZone>,
+}
+
+impl ZoneBook {
+	pub async fn save(&self) -> eyre::Result<()> {
+		persist::save_json(ZONES_PATH, self).await
+	}
+}
+
+pub async fn zone_book() -> eyre::Result<MappedMutexGuard<'static, ZoneBook>> {
+	static ZONES: Mutex<Option<ZoneBook>> = Mutex::const_new(None);
+	let mut zones = ZONES.lock().await;
+	if zones.is_none() {
+		*zones = Some(persist::load_json(ZONES_PATH).await?);
+	}
+	Ok(MutexGuard::map(zones, |it| it.as_mut().unwrap()))
+}
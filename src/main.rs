@@ -2,6 +2,7 @@
 #![allow(clippy::from_str_radix_10)]
 #![feature(iter_intersperse, type_changing_struct_update, duration_constructors)]
 #![feature(impl_trait_in_bindings)]
+#![cfg_attr(test, feature(test))]
 
 use std::{env, ops::Deref, sync::Arc};
 
@@ -25,10 +26,15 @@ fn main() -> eyre::Result<()> {
 	_ = dotenv::dotenv();
 	tracing_subscriber::fmt::init();
 	tracing::info!("Creating async runtime");
-	tokio::runtime::Builder::new_multi_thread()
+	let runtime = tokio::runtime::Builder::new_multi_thread()
 		.enable_all()
-		.build()?
-		.block_on(amain())
+		.build()?;
+	let args = env::args().skip(1).collect::<Vec<_>>();
+	match args.first().map(String::as_str) {
+		None => runtime.block_on(amain()),
+		Some("tag") => runtime.block_on(features::tags::cli::run(&args[1..])),
+		Some(command) => eyre::bail!("unknown command {command}. usage: helios [tag ...]"),
+	}
 }
 async fn amain() -> eyre::Result<()> {
 	tracing::info!("Booting up");
@@ -36,6 +42,7 @@ async fn amain() -> eyre::Result<()> {
 	let intents = Intents::MESSAGE_CONTENT
 		| Intents::DIRECT_MESSAGES
 		| Intents::GUILD_MESSAGES
+		| Intents::GUILD_MESSAGE_REACTIONS
 		| Intents::GUILDS;
 
 	let client = Client::builder()
@@ -4,10 +4,23 @@ use crate::fixed_regex;
 
 pub fn chomp_user(line: &str) -> Option<(Id<UserMarker>, &str)> {
 	fixed_regex!(USER_REGEX = "<@([0-9]+)>");
-	USER_REGEX.captures_at(line, 0).map(|it| {
-		(
-			Id::new((it.get(1).unwrap()).as_str().parse().unwrap()),
-			line[it.get(0).unwrap().end()..].trim_start(),
-		)
-	})
+	let captures = USER_REGEX.captures_at(line, 0)?;
+	// Ids of zero or beyond u64 can be typed, but belong to nobody
+	let id = Id::new_checked(captures.get(1)?.as_str().parse().ok()?)?;
+	Some((id, line[captures.get(0)?.end()..].trim_start()))
+}
+
+#[cfg(test)]
+mod tests {
+	use twilight_model::id::Id;
+
+	use crate::utils::args::chomp_user;
+
+	#[test]
+	fn test_chomp_user() {
+		assert_eq!(chomp_user("<@123> 5"), Some((Id::new(123), "5")));
+		assert_eq!(chomp_user("<@0>"), None);
+		assert_eq!(chomp_user("<@99999999999999999999>"), None);
+		assert_eq!(chomp_user("berlin"), None);
+	}
 }
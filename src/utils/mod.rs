@@ -5,7 +5,10 @@ use twilight_model::{
 		Message,
 		message::{MessageReference, MessageReferenceType},
 	},
-	id::{Id, marker::MessageMarker},
+	id::{
+		Id,
+		marker::{MessageMarker, RoleMarker},
+	},
 	user::User,
 };
 
@@ -18,6 +21,7 @@ pub mod args;
 pub mod cached;
 pub mod consts;
 pub mod dynroles;
+pub mod persist;
 pub trait UserExt {
 	fn mention(&self) -> String;
 }
@@ -59,18 +63,23 @@ impl AuthorPerms {
 }
 
 pub fn author_perms<T: Deref<Target = Message>>(msg: &EventWithContext<&T>) -> AuthorPerms {
-	if msg.author.bot {
+	let member = msg.cache.member(FIRMAMENT_SERVER, msg.author.id);
+	user_perms(&msg.author, member.as_ref().map(|it| it.roles()))
+}
+
+/// Permissions of a user, given their roles on the Firmament server if they are known.
+pub fn user_perms(user: &User, roles: Option<&[Id<RoleMarker>]>) -> AuthorPerms {
+	if user.bot {
 		return AuthorPerms::Ignore;
 	}
-	let author_id = msg.author.id;
-	if author_id == THE_BIG_RAT {
+	if user.id == THE_BIG_RAT {
 		return AuthorPerms::Obey;
 	}
-	if let Some(member) = msg.cache.member(FIRMAMENT_SERVER, author_id) {
-		if member.roles().contains(&DISREGARD_ROLE) {
+	if let Some(roles) = roles {
+		if roles.contains(&DISREGARD_ROLE) {
 			return AuthorPerms::Ignore;
 		}
-		if member.roles().contains(&OBEY_ROLE) {
+		if roles.contains(&OBEY_ROLE) {
 			return AuthorPerms::Obey;
 		}
 	}
@@ -95,6 +104,15 @@ impl BoxedEventHandler {
 
 inventory::collect!(BoxedEventHandler);
 
+/// A `!command` some feature handles, so that tags know not to treat it as a misspelled tag name.
+pub struct ClaimedCommand(pub &'static str);
+
+inventory::collect!(ClaimedCommand);
+
+pub fn is_claimed_command(name: &str) -> bool {
+	inventory::iter::<ClaimedCommand>().any(|it| it.0 == name)
+}
+
 #[macro_export]
 macro_rules! handle_all {
 	($handler:ident) => {
@@ -118,6 +136,15 @@ macro_rules! handle {
 	};
 }
 
+#[macro_export]
+macro_rules! claim_command {
+	($name:literal) => {
+		::inventory::submit! {
+			$crate::utils::ClaimedCommand($name)
+		}
+	};
+}
+
 #[macro_export]
 macro_rules! handle_message {
 	($condition:ident, $handler:expr) => {
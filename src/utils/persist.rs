@@ -0,0 +1,35 @@
+use std::path::Path;
+
+use eyre::Context as _;
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Load a json file, falling back to the default value if it does not exist yet.
+pub async fn load_json<T: DeserializeOwned + Default>(path: impl AsRef<Path>) -> eyre::Result<T> {
+	let path = path.as_ref();
+	match tokio::fs::read(path).await {
+		Ok(bytes) => {
+			serde_json::from_slice(&bytes).wrap_err_with(|| format!("parsing {}", path.display()))
+		}
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+		Err(err) => Err(err).wrap_err_with(|| format!("reading {}", path.display())),
+	}
+}
+
+/// Save a json file. Writes to a temporary file first, so that a crash never leaves a half written file behind.
+pub async fn save_json<T: Serialize>(path: impl AsRef<Path>, value: &T) -> eyre::Result<()> {
+	save_bytes(path, serde_json::to_vec_pretty(value)?).await
+}
+
+/// Save a file through a temporary file, like [`save_json`].
+pub async fn save_bytes(path: impl AsRef<Path>, bytes: Vec<u8>) -> eyre::Result<()> {
+	let path = path.as_ref();
+	if let Some(parent) = path.parent() {
+		tokio::fs::create_dir_all(parent).await?;
+	}
+	let temp_path = path.with_extension("tmp");
+	tokio::fs::write(&temp_path, bytes).await?;
+	tokio::fs::rename(&temp_path, path)
+		.await
+		.wrap_err_with(|| format!("writing {}", path.display()))?;
+	Ok(())
+}